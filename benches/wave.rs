@@ -58,5 +58,54 @@ fn collapse(c: &mut Criterion) {
     });
 }
 
+/// Compare the serial solver to the parallel one on a large grid.
+#[cfg(feature = "rayon")]
+fn regions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collapse 100x100");
+    group.sample_size(10);
+    let assets = load_assets("basic");
+
+    group.bench_function("serial", |b| {
+        let mut wave = Wave::new(&assets, black_box(100), black_box(100));
+        b.iter(|| {
+            wave.initialize();
+            while !wave.is_collapsed() {
+                if wave.collapse(1000).is_err() {
+                    wave.initialize();
+                }
+            }
+        })
+    });
+    group.bench_function("regions", |b| {
+        let wave = Wave::new(&assets, black_box(100), black_box(100));
+        b.iter(|| {
+            for seed in 0.. {
+                if wave.clone().collapse_regions(25, seed).is_ok() {
+                    break;
+                }
+            }
+        })
+    });
+    group.finish();
+
+    // Too large for the serial solver, only the regions are measured.
+    let mut group = c.benchmark_group("collapse 1000x1000");
+    group.sample_size(10);
+    group.bench_function("regions", |b| {
+        let wave = Wave::new(&assets, black_box(1000), black_box(1000));
+        b.iter(|| {
+            for seed in 0.. {
+                if wave.clone().collapse_regions(50, seed).is_ok() {
+                    break;
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+#[cfg(not(feature = "rayon"))]
 criterion_group!(benches, initialize, least_entropy, collapse);
+#[cfg(feature = "rayon")]
+criterion_group!(benches, initialize, least_entropy, collapse, regions);
 criterion_main!(benches);
@@ -3,7 +3,7 @@
 //! `cargo run --release --example gen_image basic 20 20`
 
 #[cfg(feature = "image")]
-use image::{DynamicImage, GenericImageView};
+use image::DynamicImage;
 
 #[cfg(feature = "image")]
 fn main() {
@@ -12,11 +12,14 @@ fn main() {
 
     // Load all the assets
     let assets = load_assets(&asset_path);
-    // Tile size
-    let asset_size = assets[0].dimensions().0 as usize;
 
-    // Create and initialize wave.
-    let mut wave = billow::Wave::new(&assets, width, height);
+    let wave = generate(billow::Wave::new(&assets, width, height));
+    create_image(&assets, &wave);
+}
+
+/// Collapse the wave, starting over until it succeeds.
+#[cfg(all(feature = "image", not(feature = "rayon")))]
+fn generate(mut wave: billow::Wave) -> billow::Wave {
     wave.initialize();
 
     loop {
@@ -24,12 +27,18 @@ fn main() {
             break;
         }
 
-        if let Err(_) = wave.collapse(100) {
+        if wave.collapse(100).is_err() {
             // If the wave is not collapsed, start over.
             wave.initialize();
         }
     }
-    create_image(&assets, &wave, asset_size, width, height);
+    wave
+}
+
+/// Collapse the wave, trying 64 seeds on every core.
+#[cfg(all(feature = "image", feature = "rayon"))]
+fn generate(wave: billow::Wave) -> billow::Wave {
+    wave.collapse_parallel(64, rand::random()).unwrap()
 }
 
 /// Helper function to load images from a folder.
@@ -56,39 +65,15 @@ fn load_assets(folder: &str) -> Vec<DynamicImage> {
 
 /// Helper function to save the generated image.
 #[cfg(feature = "image")]
-fn create_image(
-    assets: &Vec<DynamicImage>,
-    wave: &billow::Wave,
-    s: usize,
-    width: usize,
-    height: usize,
-) {
-    use image::ImageBuffer;
-
-    let mut container = DynamicImage::new_rgba8((s * width) as u32, (s * height) as u32);
-
-    wave.grid.iter().for_each(|slot| {
-        let top = match slot.superposition.len() {
-            // If there is only one image, just use it.
-            1 => assets[slot.superposition[0].index].clone(),
-            // If there isn't any image, use a red square. This is to mark the failed slot
-            0 => DynamicImage::ImageRgb8(ImageBuffer::from_pixel(
-                s as u32,
-                s as u32,
-                image::Rgb([75, 15, 15]),
-            )),
-            // If there is more than one image, use a black square.
-            _ => DynamicImage::new_rgb8(s as u32, s as u32),
-        };
-        // Draw the slot iamge on the container.
-        image::imageops::overlay(
-            &mut container,
-            &top,
-            (slot.x * s) as i64,
-            (slot.y * s) as i64,
-        );
-    });
-    container.save("examples/output.png").unwrap();
+fn create_image(assets: &[DynamicImage], wave: &billow::Wave) {
+    let options = billow::RenderOptions {
+        // If there is more than one image, use a black square.
+        uncollapsed: billow::Uncollapsed::Color([0, 0, 0, 255]),
+        ..Default::default()
+    };
+    billow::render(wave, assets, &options)
+        .save("examples/output.png")
+        .unwrap();
     println!("Image saved to examples/output.png");
 }
 
@@ -1,9 +1,9 @@
 //! An example of calculating the connectorID.
 //!
-//! `cargo run --release --example print_connectors`
+//! `cargo run --release --example print_connectors`, add `-- --dot` for a Graphviz graph.
 
 #[cfg(feature = "image")]
-use billow::{get_constraint_fn, Direction, Image};
+use billow::{get_constraint_fn, Direction, DotOptions, Image, Wave};
 
 /// Helper function to load images from a folder.
 #[cfg(feature = "image")]
@@ -27,6 +27,14 @@ fn load_assets(path: &str) -> (Vec<String>, Vec<impl Image>) {
 #[cfg(feature = "image")]
 fn main() {
     let assets = load_assets("assets/basic");
+    let wave = Wave::with_custom_constraint(&assets.1, 1, 1, get_constraint_fn(1));
+
+    // Print the adjacency graph instead, e.g. `... -- --dot | dot -Tsvg > graph.svg`.
+    if std::env::args().any(|arg| arg == "--dot") {
+        let options = DotOptions::default().names(assets.0.clone()).clusters(true);
+        print!("{}", wave.to_dot(&options));
+        return;
+    }
 
     let constraint_fn = get_constraint_fn(1);
 
@@ -47,6 +55,12 @@ fn main() {
         }
         println!("|{empty:.<15}|{empty:.<15}|{empty:.<15}|", empty = "");
     }
+
+    // Report the connectors and modules that make the tileset fail.
+    println!("\n{}", wave.analyze());
+    for (idx, name) in assets.0.iter().enumerate() {
+        println!("Module {}: {}", idx, name);
+    }
 }
 
 #[cfg(not(feature = "image"))]
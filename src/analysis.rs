@@ -0,0 +1,240 @@
+//! Find the modules and connectors that make a tileset fail.
+
+use std::fmt;
+
+use crate::{
+    conector::ConnectorID, direction::Direction, module::Module, slot::Slot, wave::PossibleFn,
+};
+
+/// Sides of the modules that share a connector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectorGroup {
+    pub connector: ConnectorID,
+    /// Label of the connector, or its hexadecimal ID.
+    pub name: String,
+    /// Module index and side.
+    pub sides: Vec<(usize, Direction)>,
+}
+
+/// Report of the problems of a set of modules, see [`Wave::analyze`](crate::Wave::analyze).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Analysis {
+    /// Connectors found on a side that no module has on the opposite side.
+    pub unmatched: Vec<(ConnectorID, Direction)>,
+    /// Modules that can't have any neighbor in a direction.
+    pub dead_ends: Vec<(usize, Direction)>,
+    /// Modules that can never be placed in a grid with neighbors on every side.
+    /// Includes the dead ends and the modules that only fit next to unreachable modules.
+    pub unreachable: Vec<usize>,
+    /// Every connector and the sides it appears on.
+    pub groups: Vec<ConnectorGroup>,
+}
+
+impl Analysis {
+    /// Analyze modules, using `is_possible_fn` to decide which ones fit together.
+    pub(crate) fn new(
+        modules: &[Module],
+        is_possible_fn: &PossibleFn,
+        name: &dyn Fn(ConnectorID) -> String,
+    ) -> Self {
+        let mut analysis = Analysis::default();
+
+        for module in modules {
+            for direction in Direction::all() {
+                let connector = module.connectors[direction as usize];
+                match analysis
+                    .groups
+                    .iter_mut()
+                    .find(|group| group.connector == connector)
+                {
+                    Some(group) => group.sides.push((module.index, direction)),
+                    None => analysis.groups.push(ConnectorGroup {
+                        connector,
+                        name: name(connector),
+                        sides: vec![(module.index, direction)],
+                    }),
+                }
+            }
+        }
+        for group in &analysis.groups {
+            for &(_, direction) in &group.sides {
+                let back = direction.reverse();
+                let matched = group.sides.iter().any(|&(_, side)| side == back);
+                if !matched && !analysis.unmatched.contains(&(group.connector, direction)) {
+                    analysis.unmatched.push((group.connector, direction));
+                }
+            }
+        }
+
+        // fits[a][d][b]: module `b` can be placed in direction `d` of module `a`.
+        let slot = |module: &Module| Slot {
+            superposition: vec![*module],
+            ..Default::default()
+        };
+        let fits: Vec<Vec<Vec<bool>>> = modules
+            .iter()
+            .map(|a| {
+                Direction::all()
+                    .into_iter()
+                    .map(|direction| {
+                        modules
+                            .iter()
+                            .map(|b| is_possible_fn(*b, slot(a), slot(b), direction))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (a, fits) in fits.iter().enumerate() {
+            for direction in Direction::all() {
+                if !fits[direction as usize].contains(&true) {
+                    analysis.dead_ends.push((modules[a].index, direction));
+                }
+            }
+        }
+
+        // Remove the modules without neighbors until every remaining one has some.
+        let mut alive = vec![true; modules.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for a in 0..modules.len() {
+                let stuck = alive[a]
+                    && fits[a].iter().any(|fits| {
+                        !fits
+                            .iter()
+                            .zip(alive.iter())
+                            .any(|(&fits, &alive)| fits && alive)
+                    });
+                if stuck {
+                    alive[a] = false;
+                    changed = true;
+                }
+            }
+        }
+        analysis.unreachable = modules
+            .iter()
+            .zip(alive)
+            .filter(|(_, alive)| !alive)
+            .map(|(module, _)| module.index)
+            .collect();
+
+        analysis
+    }
+
+    /// Returns true if no problem was found.
+    pub fn is_ok(&self) -> bool {
+        self.unmatched.is_empty() && self.dead_ends.is_empty() && self.unreachable.is_empty()
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Connectors:")?;
+        for group in &self.groups {
+            let sides: Vec<String> = group
+                .sides
+                .iter()
+                .map(|(module, direction)| format!("{} {}", module, direction))
+                .collect();
+            writeln!(f, "  {}: {}", group.name, sides.join(", "))?;
+        }
+
+        if self.is_ok() {
+            return writeln!(f, "No problems found.");
+        }
+        for (connector, direction) in &self.unmatched {
+            let connector = match self.groups.iter().find(|g| g.connector == *connector) {
+                Some(group) => group.name.clone(),
+                None => connector.to_string(),
+            };
+            writeln!(
+                f,
+                "Connector {} on the {} side never matches the {} side of a module.",
+                connector,
+                direction,
+                direction.reverse()
+            )?;
+        }
+        for (module, direction) in &self.dead_ends {
+            writeln!(
+                f,
+                "Module {} has no possible neighbor {}.",
+                module, direction
+            )?;
+        }
+        for module in &self.unreachable {
+            writeln!(f, "Module {} can never be placed.", module)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Wave;
+    use std::sync::Arc;
+
+    fn module(index: usize, connectors: [&str; 4]) -> Module {
+        Module {
+            index,
+            connectors: connectors.map(ConnectorID::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let wave = Wave::from_modules(
+            vec![
+                module(0, ["a", "a", "a", "a"]),
+                // Nothing has `z` on the up side.
+                module(1, ["a", "b", "z", "a"]),
+                // Only fits next to itself.
+                module(2, ["c", "c", "c", "c"]),
+                // Only fits on the right of 1.
+                module(3, ["c", "c", "c", "b"]),
+            ],
+            3,
+            3,
+        );
+        let analysis = wave.analyze();
+
+        assert_eq!(
+            analysis.unmatched,
+            vec![(ConnectorID::from("z"), Direction::Down)]
+        );
+        assert_eq!(analysis.dead_ends, vec![(1, Direction::Down)]);
+        assert_eq!(analysis.unreachable, vec![1, 3]);
+        assert_eq!(analysis.groups.len(), 4);
+        assert_eq!(
+            analysis.groups[1].sides,
+            vec![(1, Direction::Right), (3, Direction::Left)]
+        );
+        assert!(!analysis.is_ok());
+    }
+
+    #[test]
+    fn test_dead_ends() {
+        let mut wave = Wave::from_modules(
+            vec![
+                module(0, ["a", "a", "a", "a"]),
+                module(1, ["a", "a", "a", "a"]),
+            ],
+            3,
+            3,
+        );
+        assert!(wave.analyze().is_ok());
+
+        // Nothing can be placed below module 1.
+        wave.is_possible_fn = Arc::new(|module, from, _to, direction| {
+            !(from.superposition[0].index == 1 && direction == Direction::Down)
+                && module.is_possible(&from, direction)
+        });
+        let analysis = wave.analyze();
+        assert_eq!(analysis.dead_ends, vec![(1, Direction::Down)]);
+        assert_eq!(analysis.unreachable, vec![1]);
+    }
+}
@@ -0,0 +1,326 @@
+//! Generate a grid from a tileset and save it as an image, an animation, a JSON grid or a Tiled map.
+//!
+//! `cargo run --release --features cli -- assets/basic/tileset.json 20 20 --seed 42 -o output.png`
+//!
+//! Exit codes:
+//! - `1`: the tileset could not be loaded.
+//! - `2`: invalid arguments.
+//! - `3`: every attempt ended in a contradiction.
+//! - `4`: the output could not be written.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use billow::{Heuristic, Recorder, RenderOptions, TiledMap, Tileset, Wave};
+use clap::{Parser, ValueEnum};
+use image::DynamicImage;
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Generate a grid with the wave function collapse algorithm"
+)]
+struct Args {
+    /// Tileset file (`.json`, or Gumin `.xml` with the `gumin` feature), or a folder of images.
+    input: PathBuf,
+    /// Width of the grid, in slots.
+    width: usize,
+    /// Height of the grid, in slots.
+    height: usize,
+    /// Seed of the random number generator.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// How the next slot to collapse is chosen.
+    #[arg(long, value_enum, default_value_t = HeuristicArg::Entropy)]
+    heuristic: HeuristicArg,
+    /// Number of generations tried before giving up on contradictions.
+    #[arg(long, default_value_t = 50)]
+    attempts: usize,
+    /// Make the edges of the grid wrap around.
+    #[arg(long)]
+    periodic: bool,
+    /// Output file.
+    #[arg(short, long, default_value = "output.png")]
+    output: PathBuf,
+    /// Output format. Guessed from the extension of the output file by default.
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HeuristicArg {
+    Entropy,
+    Scanline,
+    Random,
+}
+
+impl From<HeuristicArg> for Heuristic {
+    fn from(heuristic: HeuristicArg) -> Self {
+        match heuristic {
+            HeuristicArg::Entropy => Heuristic::Entropy,
+            HeuristicArg::Scanline => Heuristic::Scanline,
+            HeuristicArg::Random => Heuristic::Random,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Png,
+    Gif,
+    Json,
+    Tmx,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Format::Png),
+            "gif" => Some(Format::Gif),
+            "json" => Some(Format::Json),
+            "tmx" => Some(Format::Tmx),
+            _ => None,
+        }
+    }
+}
+
+/// Reason why the generation failed.
+enum Error {
+    Input(String),
+    Arguments(String),
+    Contradiction(String),
+    Output(String),
+}
+
+impl Error {
+    fn code(&self) -> u8 {
+        match self {
+            Error::Input(_) => 1,
+            Error::Arguments(_) => 2,
+            Error::Contradiction(_) => 3,
+            Error::Output(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Input(err) => write!(f, "Failed to load the tileset: {}", err),
+            Error::Arguments(err) => write!(f, "{}", err),
+            Error::Contradiction(err) => write!(f, "Failed to generate the grid: {}", err),
+            Error::Output(err) => write!(f, "Failed to write the output: {}", err),
+        }
+    }
+}
+
+/// Modules and how to draw them.
+struct Input {
+    names: Vec<String>,
+    tiles: Vec<Option<DynamicImage>>,
+    map: TiledMap,
+    tileset: Option<Tileset>,
+}
+
+impl Input {
+    /// Load the modules. Image paths of the map are relative to `output`.
+    fn load(path: &Path, output: &Path) -> Result<Self, Error> {
+        if path.is_dir() {
+            return Input::from_folder(path, output);
+        }
+
+        let tileset = match path.extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "gumin")]
+            Some("xml") => Tileset::load_gumin(path),
+            _ => Tileset::load(path),
+        }
+        .map_err(|e| Error::Input(e.to_string()))?;
+
+        let tiles = (0..tileset.len())
+            .map(|idx| tileset.image(idx).map(DynamicImage::ImageRgba8))
+            .collect::<Vec<_>>();
+        let size = tiles.iter().flatten().next().map_or((1, 1), |tile| {
+            (tile.width() as usize, tile.height() as usize)
+        });
+        Ok(Input {
+            names: tileset.names.clone(),
+            tiles,
+            map: TiledMap::from_tileset(&tileset, size, output),
+            tileset: Some(tileset),
+        })
+    }
+
+    /// Every image of the folder is a module, in alphabetical order.
+    fn from_folder(path: &Path, output: &Path) -> Result<Self, Error> {
+        let mut paths = fs::read_dir(path)
+            .map_err(|e| Error::Input(e.to_string()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                matches!(
+                    path.extension().and_then(|e| e.to_str()),
+                    Some("png" | "jpg")
+                )
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut tiles = vec![];
+        for path in &paths {
+            let image = image::open(path)
+                .map_err(|e| Error::Input(format!("{}: {}", path.display(), e)))?;
+            tiles.push(Some(image));
+        }
+        let size = tiles.first().map_or((1, 1), |tile: &Option<DynamicImage>| {
+            let tile = tile.as_ref().expect("Folder images are loaded");
+            (tile.width() as usize, tile.height() as usize)
+        });
+        let names = paths
+            .iter()
+            .map(|path| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        let images = paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+
+        Ok(Input {
+            names,
+            tiles,
+            map: TiledMap::new(images, size).relative_to(output),
+            tileset: None,
+        })
+    }
+
+    fn wave(&self, width: usize, height: usize) -> Result<Wave, Error> {
+        match &self.tileset {
+            Some(tileset) => Ok(tileset.wave(width, height)),
+            None if self.tiles.is_empty() => Err(Error::Input(String::from("no images found"))),
+            None => {
+                let tiles: Vec<_> = self.tiles.iter().flatten().cloned().collect();
+                Ok(Wave::new(&tiles, width, height))
+            }
+        }
+    }
+
+    /// Images of the modules, required to draw the grid.
+    fn images(&self) -> Result<Vec<DynamicImage>, Error> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(|(idx, tile)| {
+                tile.clone().ok_or_else(|| {
+                    Error::Output(format!("module \"{}\" has no image", self.names[idx]))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Collapse the wave, restarting after a contradiction.
+fn generate(
+    args: &Args,
+    wave: &mut Wave,
+    mut recorder: Option<&mut Recorder<DynamicImage>>,
+) -> Result<(), Error> {
+    let mut error = String::from("no attempts");
+    for _ in 0..args.attempts {
+        if let Some(recorder) = recorder.as_deref_mut() {
+            recorder.clear();
+        }
+        wave.initialize();
+        match solve(wave, recorder.as_deref_mut()) {
+            Ok(()) => return Ok(()),
+            Err(err) => error = err,
+        }
+    }
+    Err(Error::Contradiction(error))
+}
+
+/// Collapse the wave once, recording every step.
+fn solve(wave: &mut Wave, mut recorder: Option<&mut Recorder<DynamicImage>>) -> Result<(), String> {
+    while !wave.is_collapsed() {
+        wave.collapse_with(1000, |wave| {
+            if let Some(recorder) = recorder.as_deref_mut() {
+                recorder.observe(wave);
+            }
+        })?;
+    }
+    Ok(())
+}
+
+fn run(args: &Args) -> Result<(), Error> {
+    let format = match args.format.or_else(|| Format::from_path(&args.output)) {
+        Some(format) => format,
+        None => {
+            return Err(Error::Arguments(format!(
+                "Unknown output format for {}, use --format",
+                args.output.display()
+            )))
+        }
+    };
+
+    let input = Input::load(&args.input, &args.output)?;
+    let mut wave = input.wave(args.width, args.height)?;
+    if let Some(seed) = args.seed {
+        wave.set_seed(seed);
+    }
+    wave.set_heuristic(args.heuristic.into());
+    wave.set_periodic(args.periodic);
+
+    let output = |result: Result<(), String>| result.map_err(Error::Output);
+    match format {
+        Format::Png => {
+            let images = input.images()?;
+            generate(args, &mut wave, None)?;
+            let image = billow::render(&wave, &images, &RenderOptions::default());
+            output(image.save(&args.output).map_err(|e| e.to_string()))
+        }
+        Format::Gif => {
+            let images = input.images()?;
+            let mut recorder = Recorder::new(&images);
+            generate(args, &mut wave, Some(&mut recorder))?;
+            recorder.capture(&wave);
+            output(recorder.save(&args.output).map_err(|e| e.to_string()))
+        }
+        Format::Json => {
+            generate(args, &mut wave, None)?;
+            let grid: Vec<Vec<usize>> = wave
+                .grid
+                .chunks(wave.width().max(1))
+                .map(|row| row.iter().map(|slot| slot.superposition[0].index).collect())
+                .collect();
+            let json = serde_json::json!({
+                "width": wave.width(),
+                "height": wave.height(),
+                "modules": input.names,
+                "grid": grid,
+            });
+            output(fs::write(&args.output, json.to_string()).map_err(|e| e.to_string()))
+        }
+        Format::Tmx => {
+            generate(args, &mut wave, None)?;
+            let tmx = input.map.to_tmx(&wave);
+            output(fs::write(&args.output, tmx).map_err(|e| e.to_string()))
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(err.code())
+        }
+    }
+}
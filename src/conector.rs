@@ -1,9 +1,19 @@
 use sha2::Digest;
 use sha2::Sha256;
-use std::fmt;
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    edge::{EdgeConstraint, EdgeSampling},
+    wave::ConstraintFn,
+};
 
 /// A connector is a unique identifier for a specific image and direction.
-#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Default, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectorID([u8; 4]);
 
 impl ConnectorID {
@@ -19,6 +29,15 @@ impl ConnectorID {
         hasher.update(id);
         Self(hasher.finalize()[..4].try_into().unwrap())
     }
+
+    /// Create a connector from a list of connectors. The connector of a single one is itself.
+    pub(crate) fn combine<'a>(ids: impl Iterator<Item = &'a ConnectorID>) -> Self {
+        let ids: Vec<&ConnectorID> = ids.collect();
+        match ids.as_slice() {
+            [id] => **id,
+            _ => ConnectorID::from(ids.iter().flat_map(|id| id.0).collect::<Vec<u8>>()),
+        }
+    }
 }
 
 impl fmt::Display for ConnectorID {
@@ -31,6 +50,123 @@ impl fmt::Display for ConnectorID {
     }
 }
 
+/// Returns a readable label for the source of a connector.
+/// Text is kept as is, anything else is written as RGBA colors.
+fn label(source: &[u8]) -> String {
+    match std::str::from_utf8(source) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+        _ if source.len() % 4 == 0 => source
+            .chunks(4)
+            .map(|c| format!("#{:02X}{:02X}{:02X}{:02X}", c[0], c[1], c[2], c[3]))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => source.iter().map(|b| format!("{:02X}", b)).collect(),
+    }
+}
+
+/// Two different sources that hash to the same connector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    pub id: ConnectorID,
+    /// Labels of the colliding sources.
+    pub sources: Vec<String>,
+}
+
+#[derive(Default)]
+struct Registry {
+    sources: HashMap<ConnectorID, Vec<u8>>,
+    collisions: Vec<Collision>,
+}
+
+/// Remembers the source of every connector created through it, to name them and find collisions.
+///
+/// The registry is a shared handle: clones record into the same registry.
+/// ```
+/// use billow::{ConnectorID, ConnectorRegistry};
+///
+/// let registry = ConnectorRegistry::new();
+/// let id = registry.register("left");
+/// assert_eq!(id, ConnectorID::from("left"));
+/// assert_eq!(registry.name(id), "left");
+/// assert!(registry.collisions().is_empty());
+/// ```
+#[derive(Clone, Default)]
+pub struct ConnectorRegistry {
+    inner: Arc<Mutex<Registry>>,
+}
+
+impl ConnectorRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the connector of a source and remember it.
+    pub fn register(&self, source: impl AsRef<[u8]>) -> ConnectorID {
+        let source = source.as_ref();
+        let id = ConnectorID::from(source);
+
+        let mut registry = self.inner.lock().expect("Connector registry poisoned");
+        let registry = &mut *registry;
+        match registry.sources.get(&id) {
+            None => {
+                registry.sources.insert(id, source.to_vec());
+            }
+            Some(known) if known.as_slice() != source => {
+                let source = label(source);
+                match registry.collisions.iter_mut().find(|c| c.id == id) {
+                    Some(collision) if collision.sources.contains(&source) => {}
+                    Some(collision) => collision.sources.push(source),
+                    None => registry.collisions.push(Collision {
+                        id,
+                        sources: vec![label(known), source],
+                    }),
+                }
+            }
+            Some(_) => {}
+        }
+        id
+    }
+
+    /// Returns the label of the source of a connector, if it was registered.
+    pub fn label(&self, id: ConnectorID) -> Option<String> {
+        let registry = self.inner.lock().expect("Connector registry poisoned");
+        registry.sources.get(&id).map(|source| label(source))
+    }
+
+    /// Returns the label of a connector, or its hexadecimal ID if it is unknown.
+    pub fn name(&self, id: ConnectorID) -> String {
+        self.label(id).unwrap_or_else(|| id.to_string())
+    }
+
+    /// Returns the default constraint function, registering the sampled pixels of every side.
+    pub fn constraint_fn(&self, sample_size: usize) -> Box<ConstraintFn> {
+        EdgeConstraint::new()
+            .sampling(EdgeSampling::Points(sample_size))
+            .build_registered(self)
+    }
+
+    /// Returns the different sources found for the same connector.
+    pub fn collisions(&self) -> Vec<Collision> {
+        let registry = self.inner.lock().expect("Connector registry poisoned");
+        registry.collisions.clone()
+    }
+}
+
+impl fmt::Debug for ConnectorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let registry = self.inner.lock().expect("Connector registry poisoned");
+        f.debug_map()
+            .entries(
+                registry
+                    .sources
+                    .iter()
+                    .map(|(id, source)| (id.to_string(), label(source))),
+            )
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -66,4 +202,38 @@ mod tests {
         assert_ne!(id1, id2);
         assert_eq!(id1, ConnectorID::from("very long string"));
     }
+
+    #[test]
+    fn test_registry_label() {
+        let registry = ConnectorRegistry::new();
+        let id = registry.register([255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(registry.name(id), "#FF0000FF #0000FFFF");
+        assert_eq!(registry.label(ConnectorID::from("a")), None);
+        assert_eq!(registry.name(ConnectorID::from("a")), "CA978112");
+    }
+
+    #[test]
+    fn test_registry_collision() {
+        let registry = ConnectorRegistry::new();
+        let id = registry.register("a");
+        // Forge a collision, finding a real one takes too long for a test.
+        registry
+            .inner
+            .lock()
+            .unwrap()
+            .sources
+            .insert(ConnectorID::from("b"), b"c".to_vec());
+        registry.register("b");
+        registry.register("b");
+        registry.register("a");
+
+        assert_eq!(
+            registry.collisions(),
+            vec![Collision {
+                id: ConnectorID::from("b"),
+                sources: vec![String::from("c"), String::from("b")],
+            }]
+        );
+        assert_eq!(registry.name(id), "a");
+    }
 }
@@ -0,0 +1,442 @@
+use crate::{conector::ConnectorID, slot::Slot};
+
+/// An amount of slots, either absolute or relative to the size of the grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Limit {
+    /// A fixed number of slots.
+    Slots(usize),
+    /// A fraction of the slots of the grid, between `0.0` and `1.0`.
+    Ratio(f32),
+}
+
+impl Limit {
+    /// Returns the amount of slots for a grid with `total` slots.
+    /// Ratios are rounded up when used as a minimum and down when used as a maximum.
+    fn resolve(&self, total: usize, round_up: bool) -> usize {
+        match *self {
+            Limit::Slots(count) => count,
+            Limit::Ratio(ratio) => {
+                let count = ratio.clamp(0.0, 1.0) * total as f32;
+                if round_up {
+                    count.ceil() as usize
+                } else {
+                    count.floor() as usize
+                }
+            }
+        }
+    }
+}
+
+/// Limits how many slots of the grid can hold a module of a group.
+///
+/// Modules are identified by the position of their image in the input.
+/// ```
+/// use billow::CountConstraint;
+///
+/// let exit = CountConstraint::module(0).exactly(1);
+/// let treasure = CountConstraint::group(vec![1, 2]).at_most(3);
+/// let water = CountConstraint::module(3).at_least_ratio(0.1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountConstraint {
+    /// Modules counted by the constraint.
+    pub modules: Vec<usize>,
+    /// Minimum number of slots holding one of the modules.
+    pub min: Option<Limit>,
+    /// Maximum number of slots holding one of the modules.
+    pub max: Option<Limit>,
+}
+
+impl CountConstraint {
+    /// Create a constraint for a single module.
+    pub fn module(index: usize) -> Self {
+        Self::group(vec![index])
+    }
+
+    /// Create a constraint for a group of modules. Every slot holding any of them counts.
+    pub fn group(modules: Vec<usize>) -> Self {
+        Self {
+            modules,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Require exactly `count` slots.
+    pub fn exactly(self, count: usize) -> Self {
+        self.at_least(count).at_most(count)
+    }
+
+    /// Require at least `count` slots.
+    pub fn at_least(mut self, count: usize) -> Self {
+        self.min = Some(Limit::Slots(count));
+        self
+    }
+
+    /// Allow at most `count` slots.
+    pub fn at_most(mut self, count: usize) -> Self {
+        self.max = Some(Limit::Slots(count));
+        self
+    }
+
+    /// Require at least the given fraction of the grid.
+    pub fn at_least_ratio(mut self, ratio: f32) -> Self {
+        self.min = Some(Limit::Ratio(ratio));
+        self
+    }
+
+    /// Allow at most the given fraction of the grid.
+    pub fn at_most_ratio(mut self, ratio: f32) -> Self {
+        self.max = Some(Limit::Ratio(ratio));
+        self
+    }
+
+    /// Returns true if the module is counted by the constraint.
+    pub fn contains(&self, index: usize) -> bool {
+        self.modules.contains(&index)
+    }
+
+    /// Returns true if the slot can only hold modules of the group.
+    /// A slot without any module after a contradiction holds nothing.
+    pub(crate) fn holds(&self, slot: &Slot) -> bool {
+        !slot.superposition.is_empty() && slot.superposition.iter().all(|m| self.contains(m.index))
+    }
+
+    /// Returns true if the slot can hold any module of the group.
+    pub(crate) fn fits(&self, slot: &Slot) -> bool {
+        slot.superposition.iter().any(|m| self.contains(m.index))
+    }
+
+    /// Minimum number of slots for a grid with `total` slots.
+    pub(crate) fn min_slots(&self, total: usize) -> usize {
+        self.min.map_or(0, |limit| limit.resolve(total, true))
+    }
+
+    /// Maximum number of slots for a grid with `total` slots.
+    pub(crate) fn max_slots(&self, total: usize) -> usize {
+        self.max.map_or(total, |limit| limit.resolve(total, false))
+    }
+}
+
+/// Number of times the wave starts over when a [`ConnectivityConstraint`] breaks.
+pub const DEFAULT_RESTARTS: usize = 20;
+
+/// Requires the slots holding a module of the group to form a single connected region.
+///
+/// Two neighbor slots are connected when both hold a module of the group and their connectors match.
+/// When a partial solution can no longer be connected, the wave starts over from a new random slot,
+/// up to `restarts` times before `collapse` fails.
+/// ```
+/// use billow::ConnectivityConstraint;
+///
+/// let corridors = ConnectivityConstraint::new(vec![1, 2, 3]).restarts(50);
+/// let only_verify = ConnectivityConstraint::new(vec![1, 2, 3]).check_only();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectivityConstraint {
+    /// Walkable modules.
+    pub modules: Vec<usize>,
+    /// Only verify the collapsed grid instead of rejecting partial solutions that can't be connected.
+    pub check_only: bool,
+    /// Number of times the wave starts over before failing, unless `check_only` is set.
+    #[cfg_attr(feature = "serde", serde(default = "default_restarts"))]
+    pub restarts: usize,
+}
+
+#[cfg(feature = "serde")]
+fn default_restarts() -> usize {
+    DEFAULT_RESTARTS
+}
+
+impl ConnectivityConstraint {
+    /// Create a constraint for a group of modules.
+    pub fn new(modules: Vec<usize>) -> Self {
+        Self {
+            modules,
+            check_only: false,
+            restarts: DEFAULT_RESTARTS,
+        }
+    }
+
+    /// Verify the constraint only once the grid is collapsed, and fail instead of starting over.
+    pub fn check_only(mut self) -> Self {
+        self.check_only = true;
+        self
+    }
+
+    /// Set the number of times the wave starts over before failing. Defaults to [`DEFAULT_RESTARTS`].
+    pub fn restarts(mut self, restarts: usize) -> Self {
+        self.restarts = restarts;
+        self
+    }
+}
+
+/// Requires a route between two slots through passable connectors.
+///
+/// Consecutive slots of the route are neighbors whose shared connectors match and are passable.
+/// The length of the route is the number of steps of the shortest one.
+/// ```
+/// use billow::{ConnectorID, PathConstraint};
+///
+/// let door = ConnectorID::from("door");
+/// let path = PathConstraint::new((0, 0), (9, 9), vec![door]).max_length(30);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PathConstraint {
+    /// Coordinates of the first slot.
+    pub start: (usize, usize),
+    /// Coordinates of the last slot.
+    pub goal: (usize, usize),
+    /// Connectors that can be walked through.
+    pub connectors: Vec<ConnectorID>,
+    /// Minimum length of the shortest route.
+    pub min_length: Option<usize>,
+    /// Maximum length of the shortest route.
+    pub max_length: Option<usize>,
+}
+
+impl PathConstraint {
+    /// Create a constraint between two slots.
+    pub fn new(start: (usize, usize), goal: (usize, usize), connectors: Vec<ConnectorID>) -> Self {
+        Self {
+            start,
+            goal,
+            connectors,
+            min_length: None,
+            max_length: None,
+        }
+    }
+
+    /// Require the shortest route to take at least `length` steps.
+    pub fn min_length(mut self, length: usize) -> Self {
+        self.min_length = Some(length);
+        self
+    }
+
+    /// Require the shortest route to take at most `length` steps.
+    pub fn max_length(mut self, length: usize) -> Self {
+        self.max_length = Some(length);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        get_constraint_fn,
+        module::Module,
+        testing::{self, Edges, Tile},
+        Direction, Wave,
+    };
+
+    fn count(wave: &Wave, index: usize) -> usize {
+        wave.grid
+            .iter()
+            .filter(|slot| slot.superposition.len() == 1 && slot.superposition[0].index == index)
+            .count()
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Limit::Slots(3).resolve(100, true), 3);
+        assert_eq!(Limit::Ratio(0.15).resolve(10, true), 2);
+        assert_eq!(Limit::Ratio(0.15).resolve(10, false), 1);
+    }
+
+    #[test]
+    fn test_holds() {
+        let constraint = CountConstraint::group(vec![0, 1]);
+        let module = |index| Module {
+            index,
+            ..Default::default()
+        };
+        let slot = |modules: Vec<Module>| Slot {
+            superposition: modules,
+            ..Default::default()
+        };
+
+        assert!(constraint.holds(&slot(vec![module(0), module(1)])));
+        assert!(!constraint.holds(&slot(vec![module(0), module(2)])));
+        assert!(constraint.fits(&slot(vec![module(0), module(2)])));
+        assert!(!constraint.holds(&slot(vec![])));
+        assert!(!constraint.fits(&slot(vec![])));
+    }
+
+    #[test]
+    fn test_exactly() {
+        let tiles = [Tile(0), Tile(1), Tile(2)];
+        let mut wave = Wave::new(&tiles, 5, 5);
+        wave.add_count_constraint(CountConstraint::module(0).exactly(1));
+        wave.add_count_constraint(CountConstraint::module(1).at_most(3));
+        wave.set_seed(1);
+
+        testing::solve(&mut wave, 100);
+        assert_eq!(count(&wave, 0), 1);
+        assert!(count(&wave, 1) <= 3);
+    }
+
+    #[test]
+    fn test_at_least_ratio() {
+        let tiles = [Tile(0), Tile(1), Tile(2)];
+        let mut wave = Wave::new(&tiles, 4, 5);
+        wave.add_count_constraint(CountConstraint::group(vec![0, 1]).at_least_ratio(0.9));
+        wave.set_seed(1);
+
+        testing::solve(&mut wave, 100);
+        assert!(count(&wave, 0) + count(&wave, 1) >= 18);
+    }
+
+    #[test]
+    fn test_is_connected() {
+        let mut wave = Wave::new(&[Tile(0)], 3, 1);
+        wave.initialize();
+
+        let connectors = |sides: [&str; 4]| sides.map(ConnectorID::from);
+        let blank = Module {
+            index: 0,
+            connectors: connectors(["wall", "wall", "wall", "wall"]),
+            ..Default::default()
+        };
+        let corridor = Module {
+            index: 1,
+            connectors: connectors(["wall", "door", "wall", "door"]),
+            ..Default::default()
+        };
+        let dead_end = Module {
+            index: 1,
+            connectors: connectors(["door", "wall", "wall", "wall"]),
+            ..Default::default()
+        };
+        let set = |wave: &mut Wave, modules: [Module; 3]| {
+            for (slot, module) in wave.grid.iter_mut().zip(modules) {
+                slot.superposition = vec![module];
+            }
+        };
+
+        set(&mut wave, [corridor, corridor, corridor]);
+        assert!(wave.is_connected(&[1]));
+
+        set(&mut wave, [corridor, blank, corridor]);
+        assert!(!wave.is_connected(&[1]));
+
+        // Walkable neighbors that don't share a connector.
+        set(&mut wave, [corridor, dead_end, blank]);
+        assert!(!wave.is_connected(&[1]));
+    }
+
+    #[test]
+    fn test_connectivity() {
+        // blank, horizontal, vertical and cross corridors.
+        let tiles = [
+            Edges([0, 0, 0, 0]),
+            Edges([0, 1, 0, 1]),
+            Edges([1, 0, 1, 0]),
+            Edges([1, 1, 1, 1]),
+        ];
+        let wave = |constraint: ConnectivityConstraint, seed| {
+            let mut wave = Wave::new(&tiles, 5, 5);
+            wave.add_connectivity_constraint(constraint);
+            wave.set_seed(seed);
+            wave.initialize();
+            wave
+        };
+
+        // The wave starts over by itself when the corridors can no longer be connected.
+        for seed in 0..20 {
+            let mut wave = wave(ConnectivityConstraint::new(vec![1, 2, 3]), seed);
+            testing::run(&mut wave).unwrap();
+            assert!(wave.is_connected(&[1, 2, 3]));
+            assert!(testing::is_valid(&wave));
+        }
+
+        let mut wave = wave(ConnectivityConstraint::new(vec![1, 2, 3]).restarts(0), 6);
+        assert_eq!(
+            testing::run(&mut wave).unwrap_err(),
+            "Modules [1, 2, 3] are not connected after 0 restarts"
+        );
+    }
+
+    #[test]
+    fn test_path_length() {
+        let mut wave = Wave::new(&[Tile(0)], 3, 1);
+        wave.initialize();
+
+        let door = ConnectorID::from("door");
+        let connectors = |sides: [&str; 4]| sides.map(ConnectorID::from);
+        let blank = Module {
+            index: 0,
+            connectors: connectors(["wall", "wall", "wall", "wall"]),
+            ..Default::default()
+        };
+        let corridor = Module {
+            index: 1,
+            connectors: connectors(["wall", "door", "wall", "door"]),
+            ..Default::default()
+        };
+        wave.grid[0].superposition = vec![corridor];
+        wave.grid[1].superposition = vec![corridor, blank];
+        wave.grid[2].superposition = vec![corridor];
+
+        assert_eq!(wave.path_length((0, 0), (2, 0), &[door]), Some(2));
+        assert_eq!(wave.path_length((0, 0), (0, 0), &[door]), Some(0));
+        assert_eq!(wave.path_length((0, 0), (2, 0), &[]), None);
+
+        wave.grid[1].superposition = vec![blank];
+        assert_eq!(wave.path_length((0, 0), (2, 0), &[door]), None);
+
+        // (3, 0) is not the first slot of a next row.
+        wave.grid[1].superposition = vec![corridor];
+        assert_eq!(wave.path_length((0, 0), (3, 0), &[door]), None);
+        assert_eq!(wave.path_length((0, 1), (2, 0), &[door]), None);
+    }
+
+    #[test]
+    fn test_path() {
+        // blank, straight, corner and cross corridors.
+        let tiles = [
+            Edges([0, 0, 0, 0]),
+            Edges([0, 1, 0, 1]),
+            Edges([1, 0, 1, 0]),
+            Edges([0, 1, 1, 0]),
+            Edges([0, 0, 1, 1]),
+            Edges([1, 1, 0, 0]),
+            Edges([1, 0, 0, 1]),
+            Edges([1, 1, 1, 1]),
+        ];
+        let door = get_constraint_fn(3)(&tiles[7], Direction::Up);
+        let mut wave = Wave::new(&tiles, 4, 4);
+        wave.add_path_constraint(PathConstraint::new((0, 0), (3, 3), vec![door]).max_length(6));
+        wave.set_seed(1);
+
+        testing::solve(&mut wave, 100);
+        assert_eq!(wave.path_length((0, 0), (3, 3), &[door]), Some(6));
+    }
+
+    #[test]
+    fn test_path_outside() {
+        let mut wave = Wave::new(&[Tile(0)], 3, 2);
+        let door = ConnectorID::from("door");
+        wave.add_path_constraint(PathConstraint::new((0, 0), (3, 0), vec![door]));
+        wave.initialize();
+
+        assert_eq!(
+            wave.collapse(10).unwrap_err().to_string(),
+            "Slot (3, 0) of the path is outside the 3x2 grid"
+        );
+    }
+
+    #[test]
+    fn test_contradiction() {
+        let tiles = [Tile(0)];
+        let mut wave = Wave::new(&tiles, 2, 2);
+        wave.add_count_constraint(CountConstraint::module(0).at_most(3));
+        wave.initialize();
+
+        assert!(wave.collapse(10).is_err());
+    }
+}
@@ -5,6 +5,11 @@ use std::fmt;
 /// Also used to represent the index of the neighbors of a slot.
 /// Up = 0, Right = 1, Down = 2, Left = 3
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum Direction {
     Up,
     Right,
@@ -0,0 +1,186 @@
+//! Export of the adjacency graph of the modules in the [Graphviz](https://graphviz.org) DOT language.
+
+use std::fmt::Write;
+
+use crate::{
+    conector::ConnectorID, direction::Direction, escape, module::Module, slot::Slot,
+    wave::PossibleFn,
+};
+
+/// Options of [`Wave::to_dot`](crate::Wave::to_dot).
+/// ```
+/// use billow::DotOptions;
+///
+/// let options = DotOptions::default()
+///     .names(vec!["blank".into(), "down".into()])
+///     .clusters(true);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Label of each module. Modules without a name are labeled with their index.
+    pub names: Vec<String>,
+    /// Group the modules that can reach each other in clusters, labeled with their connectors.
+    pub clusters: bool,
+}
+
+impl DotOptions {
+    /// Set the labels of the modules.
+    pub fn names(mut self, names: Vec<String>) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Group the modules in clusters.
+    pub fn clusters(mut self, clusters: bool) -> Self {
+        self.clusters = clusters;
+        self
+    }
+}
+
+/// Returns the first module of the cluster of a module, merging the path on the way.
+fn root(cluster: &mut [usize], mut idx: usize) -> usize {
+    while cluster[idx] != idx {
+        cluster[idx] = cluster[cluster[idx]];
+        idx = cluster[idx];
+    }
+    idx
+}
+
+/// Write the graph where an edge `a -> b` means that `b` can be placed on the right of or below `a`.
+/// Left and up neighbors are the same edges in reverse.
+pub(crate) fn graph(
+    modules: &[Module],
+    is_possible_fn: &PossibleFn,
+    name: &dyn Fn(ConnectorID) -> String,
+    options: &DotOptions,
+) -> String {
+    let slot = |module: &Module| Slot {
+        superposition: vec![*module],
+        ..Default::default()
+    };
+
+    let mut edges = vec![];
+    for (a, from) in modules.iter().enumerate() {
+        for direction in [Direction::Right, Direction::Down] {
+            for (b, to) in modules.iter().enumerate() {
+                if is_possible_fn(*to, slot(from), slot(to), direction) {
+                    edges.push((a, b, direction, from.connectors[direction as usize]));
+                }
+            }
+        }
+    }
+
+    // Modules linked by an edge share a cluster.
+    let mut cluster: Vec<usize> = (0..modules.len()).collect();
+    for &(a, b, _, _) in &edges {
+        let (a, b) = (root(&mut cluster, a), root(&mut cluster, b));
+        cluster[a.max(b)] = a.min(b);
+    }
+
+    let node = |idx: usize| {
+        let label = match options.names.get(idx) {
+            Some(name) => escape::dot(name),
+            None => modules[idx].index.to_string(),
+        };
+        format!("m{} [label=\"{}\"];", modules[idx].index, label)
+    };
+
+    let mut dot = String::from("digraph modules {\n");
+    if options.clusters {
+        for id in 0..modules.len() {
+            if root(&mut cluster, id) != id {
+                continue;
+            }
+            let members: Vec<usize> = (0..modules.len())
+                .filter(|&idx| root(&mut cluster, idx) == id)
+                .collect();
+            let mut connectors = vec![];
+            for &(a, _, _, connector) in &edges {
+                if members.contains(&a) && !connectors.contains(&connector) {
+                    connectors.push(connector);
+                }
+            }
+            let connectors: Vec<String> =
+                connectors.iter().map(|&c| escape::dot(&name(c))).collect();
+
+            let _ = writeln!(dot, "    subgraph cluster_{} {{", id);
+            let _ = writeln!(dot, "        label=\"{}\";", connectors.join(", "));
+            for idx in members {
+                let _ = writeln!(dot, "        {}", node(idx));
+            }
+            dot.push_str("    }\n");
+        }
+    } else {
+        for idx in 0..modules.len() {
+            let _ = writeln!(dot, "    {}", node(idx));
+        }
+    }
+
+    for (a, b, direction, connector) in edges {
+        let _ = writeln!(
+            dot,
+            "    m{} -> m{} [label=\"{} {}\"];",
+            modules[a].index,
+            modules[b].index,
+            direction,
+            escape::dot(&name(connector))
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Wave;
+
+    fn module(index: usize, connectors: [&str; 4]) -> Module {
+        Module {
+            index,
+            connectors: connectors.map(ConnectorID::from),
+            ..Default::default()
+        }
+    }
+
+    fn wave() -> Wave {
+        Wave::from_modules(
+            vec![
+                module(0, ["a", "a", "a", "a"]),
+                module(1, ["a", "b", "a", "a"]),
+                module(2, ["c", "c", "c", "b"]),
+                module(3, ["d", "d", "d", "d"]),
+            ],
+            3,
+            3,
+        )
+    }
+
+    #[test]
+    fn test_graph() {
+        let names = vec!["blank".into(), "a \"b\"\nc\u{1b}".into()];
+        let dot = wave().to_dot(&DotOptions::default().names(names));
+
+        assert!(dot.starts_with("digraph modules {\n"));
+        assert!(dot.contains("    m0 [label=\"blank\"];\n"));
+        assert!(dot.contains("    m1 [label=\"a \\\"b\\\"\\nc\u{fffd}\"];\n"));
+        assert!(dot.contains("    m2 [label=\"2\"];\n"));
+        let b = ConnectorID::from("b");
+        assert!(dot.contains(&format!("    m1 -> m2 [label=\"right {}\"];\n", b)));
+        assert!(!dot.contains("m2 -> m1"));
+        assert!(dot.contains("m3 -> m3"));
+        assert!(!dot.contains("subgraph"));
+    }
+
+    #[test]
+    fn test_clusters() {
+        let dot = wave().to_dot(&DotOptions::default().clusters(true));
+
+        // 0, 1 and 2 are linked, 3 only fits next to itself.
+        assert_eq!(dot.matches("subgraph").count(), 2);
+        assert!(dot.contains("    subgraph cluster_0 {\n"));
+        assert!(dot.contains("    subgraph cluster_3 {\n"));
+        let d = ConnectorID::from("d");
+        assert!(dot.contains(&format!("        label=\"{}\";\n        m3", d)));
+    }
+}
@@ -0,0 +1,391 @@
+//! Connectors sampled from the pixels on the sides of an image.
+
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    conector::{ConnectorID, ConnectorRegistry},
+    direction::Direction,
+    wave::{ConstraintFn, Image},
+};
+
+/// Pixels of a side that make its connector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum EdgeSampling {
+    /// `n` evenly spaced pixels, away from the corners.
+    Points(usize),
+    /// Every pixel of the side.
+    Full,
+}
+
+impl Default for EdgeSampling {
+    fn default() -> Self {
+        EdgeSampling::Points(3)
+    }
+}
+
+/// Order of the rows of pixels when more than one is read from a side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum EdgeBand {
+    /// From the side inward, so the band of a neighbor must be the mirror image across their shared edge.
+    /// Fits borders shaded the same way on both tiles, like bevels.
+    #[default]
+    Mirrored,
+    /// In the order of the image, so the band of a neighbor must be the same rows continuing past the edge.
+    Aligned,
+}
+
+/// How the sampled pixels of two sides are compared.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum EdgeMatching {
+    /// The pixels have the same RGBA values.
+    #[default]
+    Exact,
+    /// The pixels have the same colors once each channel is reduced to this number of levels.
+    Quantize(u8),
+    /// The pixels are at most this far apart, using the "redmean" color distance
+    /// that goes from 0 (same color) to about 765 (black and white).
+    /// The alpha channels can differ by the same amount.
+    Tolerance(f32),
+}
+
+/// Pixels of a side and the connector they were given.
+type KnownEdge = (Vec<[u8; 4]>, ConnectorID);
+
+/// Builder of constraint functions that compare the pixels on the sides of the images.
+/// ```
+/// use billow::{EdgeConstraint, EdgeMatching, EdgeSampling, Wave};
+/// # struct Tile;
+/// # impl billow::Image for Tile {
+/// #     fn size(&self) -> (usize, usize) { (4, 4) }
+/// #     fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] { [0; 4] }
+/// # }
+///
+/// let constraint_fn = EdgeConstraint::new()
+///     .sampling(EdgeSampling::Full)
+///     .matching(EdgeMatching::Tolerance(20.0))
+///     .build();
+/// let wave = Wave::with_custom_constraint(&[Tile, Tile], 10, 10, constraint_fn);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct EdgeConstraint {
+    /// Pixels read on each side.
+    pub sampling: EdgeSampling,
+    /// Number of rows or columns read from each side, starting at the edge.
+    pub depth: usize,
+    /// Order of the rows, see [`EdgeBand`].
+    pub band: EdgeBand,
+    /// How the pixels are compared.
+    pub matching: EdgeMatching,
+}
+
+impl Default for EdgeConstraint {
+    fn default() -> Self {
+        Self {
+            sampling: EdgeSampling::default(),
+            depth: 1,
+            band: EdgeBand::default(),
+            matching: EdgeMatching::default(),
+        }
+    }
+}
+
+impl EdgeConstraint {
+    /// Compare 3 pixels of each side exactly, like [`get_constraint_fn`](crate::get_constraint_fn).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the pixels read on each side.
+    pub fn sampling(mut self, sampling: EdgeSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Set the number of rows or columns read from each side, e.g. 3 for tiles with shaded borders.
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set the order of the rows read from each side.
+    pub fn band(mut self, band: EdgeBand) -> Self {
+        self.band = band;
+        self
+    }
+
+    /// Set how the pixels are compared.
+    pub fn matching(mut self, matching: EdgeMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Returns the constraint function.
+    ///
+    /// With [`EdgeMatching::Tolerance`] the function remembers the sides it has seen:
+    /// a side gets the connector of the first side close enough to it, in the order they were sampled.
+    pub fn build(&self) -> Box<ConstraintFn> {
+        self.build_with(|source| ConnectorID::from(source))
+    }
+
+    /// Returns the constraint function, registering the pixels of every connector.
+    pub fn build_registered(&self, registry: &ConnectorRegistry) -> Box<ConstraintFn> {
+        let registry = registry.clone();
+        self.build_with(move |source| registry.register(source))
+    }
+
+    fn build_with(&self, register: impl Fn(&[u8]) -> ConnectorID + 'static) -> Box<ConstraintFn> {
+        let edge = *self;
+        match self.matching {
+            EdgeMatching::Exact => Box::new(move |img: &dyn Image, dir: Direction| {
+                register(&edge.pixels(img, dir).concat())
+            }),
+            EdgeMatching::Quantize(levels) => Box::new(move |img: &dyn Image, dir: Direction| {
+                let pixels = edge.pixels(img, dir);
+                let pixels: Vec<[u8; 4]> = pixels
+                    .iter()
+                    .map(|pixel| pixel.map(|channel| quantize(channel, levels)))
+                    .collect();
+                register(&pixels.concat())
+            }),
+            EdgeMatching::Tolerance(distance) => {
+                let known: Arc<Mutex<Vec<KnownEdge>>> = Arc::default();
+                Box::new(move |img: &dyn Image, dir: Direction| {
+                    let pixels = edge.pixels(img, dir);
+                    let mut known = known.lock().expect("Edge constraint poisoned");
+                    let similar = known.iter().find(|(other, _)| {
+                        other.len() == pixels.len()
+                            && other
+                                .iter()
+                                .zip(&pixels)
+                                .all(|(a, b)| color_distance(*a, *b) <= distance)
+                    });
+                    match similar {
+                        Some(&(_, id)) => id,
+                        None => {
+                            let id = register(&pixels.concat());
+                            known.push((pixels, id));
+                            id
+                        }
+                    }
+                })
+            }
+        }
+    }
+
+    /// Returns the pixels of a side of the image, row by row in the order of the band.
+    /// Each row goes from left to right or from top to bottom.
+    pub(crate) fn pixels(&self, img: &dyn Image, dir: Direction) -> Vec<[u8; 4]> {
+        let (w, h) = img.size();
+        let (length, thickness) = match dir {
+            Direction::Up | Direction::Down => (w, h),
+            Direction::Right | Direction::Left => (h, w),
+        };
+        let depth = self.depth.clamp(1, thickness.max(1));
+        let along: Vec<usize> = match self.sampling {
+            EdgeSampling::Points(count) => {
+                let step = length / (count + 1); // +1 to stay away from the corners
+                (1..=count).map(|idx| idx * step).collect()
+            }
+            EdgeSampling::Full => (0..length).collect(),
+        };
+
+        let mut pixels = vec![];
+        for layer in 0..depth {
+            let inset = match (self.band, dir) {
+                (EdgeBand::Aligned, Direction::Down | Direction::Right) => depth - 1 - layer,
+                _ => layer,
+            };
+            pixels.extend(along.iter().map(|&idx| match dir {
+                Direction::Up => img.get_pixel_at(idx, inset),
+                Direction::Right => img.get_pixel_at(w - 1 - inset, idx),
+                Direction::Down => img.get_pixel_at(idx, h - 1 - inset),
+                Direction::Left => img.get_pixel_at(inset, idx),
+            }));
+        }
+        pixels
+    }
+}
+
+/// Reduce a channel to `levels` evenly spaced values.
+fn quantize(channel: u8, levels: u8) -> u8 {
+    if levels <= 1 {
+        return 0;
+    }
+    let steps = levels as u32 - 1;
+    let level = (channel as u32 * steps + 127) / 255;
+    (level * 255 / steps) as u8
+}
+
+/// Returns the largest of the "redmean" color distance and the difference of alpha.
+fn color_distance(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let mean = (a[0] as f32 + b[0] as f32) / 2.0;
+    let [r, g, b, alpha] = [0, 1, 2, 3].map(|c| a[c] as f32 - b[c] as f32);
+    let color =
+        ((2.0 + mean / 256.0) * r * r + 4.0 * g * g + (2.0 + (255.0 - mean) / 256.0) * b * b)
+            .sqrt();
+    color.max(alpha.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tile filled with one color, with a different pixel on the top side, between the sampled points.
+    struct Tile {
+        color: [u8; 4],
+        notch: [u8; 4],
+    }
+
+    impl Image for Tile {
+        fn size(&self) -> (usize, usize) {
+            (9, 9)
+        }
+        fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+            match (x, y) {
+                (3, 0) => self.notch,
+                _ => self.color,
+            }
+        }
+    }
+
+    fn tile(color: [u8; 4], notch: [u8; 4]) -> Tile {
+        Tile { color, notch }
+    }
+
+    #[test]
+    fn test_sampling() {
+        let img = tile([0, 0, 0, 255], [255, 0, 0, 255]);
+        assert_eq!(
+            EdgeConstraint::new().pixels(&img, Direction::Up),
+            vec![[0, 0, 0, 255]; 3]
+        );
+        let full = EdgeConstraint::new()
+            .sampling(EdgeSampling::Full)
+            .pixels(&img, Direction::Up);
+        assert_eq!(full.len(), 9);
+        assert_eq!(full[3], [255, 0, 0, 255]);
+
+        // The notch is missed by the points.
+        let points = EdgeConstraint::new().build();
+        let full = EdgeConstraint::new().sampling(EdgeSampling::Full).build();
+        let plain = tile([0, 0, 0, 255], [0, 0, 0, 255]);
+        assert_eq!(points(&img, Direction::Up), points(&plain, Direction::Up));
+        assert_ne!(full(&img, Direction::Up), full(&plain, Direction::Up));
+        assert_eq!(full(&img, Direction::Down), full(&plain, Direction::Up));
+    }
+
+    /// Tile with a color for each row.
+    struct Rows([[u8; 4]; 4]);
+
+    impl Image for Rows {
+        fn size(&self) -> (usize, usize) {
+            (4, 4)
+        }
+        fn get_pixel_at(&self, _x: usize, y: usize) -> [u8; 4] {
+            self.0[y]
+        }
+    }
+
+    #[test]
+    fn test_depth() {
+        let [a, b, c, d, x] = [10, 20, 30, 40, 50].map(|v| [v, v, v, 255]);
+        let tile = Rows([a, b, c, d]);
+        // Same shading on both sides of the edge.
+        let mirrored = Rows([d, c, b, a]);
+        // Same rows as `tile` continuing past the edge.
+        let aligned = Rows([c, d, x, x]);
+        let flat = Rows([d, x, x, x]);
+
+        let depth = EdgeConstraint::new().depth(2);
+        assert_eq!(
+            depth.pixels(&tile, Direction::Down),
+            [[d; 3], [c; 3]].concat()
+        );
+        let single = EdgeConstraint::new().build();
+        let depth = depth.build();
+        assert_eq!(single(&tile, Direction::Down), single(&flat, Direction::Up));
+        assert_ne!(depth(&tile, Direction::Down), depth(&flat, Direction::Up));
+        assert_eq!(
+            depth(&tile, Direction::Down),
+            depth(&mirrored, Direction::Up)
+        );
+        assert_ne!(
+            depth(&tile, Direction::Down),
+            depth(&aligned, Direction::Up)
+        );
+
+        let depth = EdgeConstraint::new()
+            .depth(2)
+            .band(EdgeBand::Aligned)
+            .build();
+        assert_eq!(
+            depth(&tile, Direction::Down),
+            depth(&aligned, Direction::Up)
+        );
+        assert_ne!(
+            depth(&tile, Direction::Down),
+            depth(&mirrored, Direction::Up)
+        );
+
+        // The depth is limited to the size of the tile.
+        let deep = EdgeConstraint::new().depth(10);
+        assert_eq!(deep.pixels(&tile, Direction::Up).len(), 12);
+    }
+
+    #[test]
+    fn test_quantize() {
+        assert_eq!(quantize(0, 4), 0);
+        assert_eq!(quantize(40, 4), 0);
+        assert_eq!(quantize(100, 4), 85);
+        assert_eq!(quantize(255, 4), 255);
+        assert_eq!(quantize(200, 1), 0);
+
+        let constraint_fn = EdgeConstraint::new()
+            .sampling(EdgeSampling::Full)
+            .matching(EdgeMatching::Quantize(4))
+            .build();
+        let a = tile([10, 20, 30, 255], [10, 20, 30, 255]);
+        let b = tile([14, 16, 35, 250], [14, 16, 35, 250]);
+        let c = tile([100, 20, 30, 255], [100, 20, 30, 255]);
+        assert_eq!(
+            constraint_fn(&a, Direction::Up),
+            constraint_fn(&b, Direction::Up)
+        );
+        assert_ne!(
+            constraint_fn(&a, Direction::Up),
+            constraint_fn(&c, Direction::Up)
+        );
+    }
+
+    #[test]
+    fn test_tolerance() {
+        assert_eq!(color_distance([1, 2, 3, 4], [1, 2, 3, 4]), 0.0);
+        assert_eq!(color_distance([0, 0, 0, 0], [0, 0, 0, 30]), 30.0);
+        assert!(color_distance([0, 0, 0, 255], [255, 255, 255, 255]) > 764.0);
+
+        let registry = ConnectorRegistry::new();
+        let constraint_fn = EdgeConstraint::new()
+            .sampling(EdgeSampling::Full)
+            .matching(EdgeMatching::Tolerance(20.0))
+            .build_registered(&registry);
+        let gray = [120, 120, 120, 255];
+        let a = tile(gray, gray);
+        // Anti-aliased notch.
+        let b = tile(gray, [126, 124, 118, 255]);
+        let c = tile(gray, [0, 0, 0, 255]);
+
+        let id = constraint_fn(&a, Direction::Up);
+        assert_eq!(constraint_fn(&b, Direction::Up), id);
+        assert_ne!(constraint_fn(&c, Direction::Up), id);
+        assert_eq!(constraint_fn(&c, Direction::Down), id);
+        assert_eq!(registry.name(id), ["#787878FF"; 9].join(" "));
+    }
+}
@@ -0,0 +1,94 @@
+//! Escaping of strings written in the exported files.
+
+use std::fmt::Write;
+
+/// Escape a string for the inside of a JSON string.
+/// Control characters are written as `\n`, `\t`, ... or `\u00XX`.
+pub(crate) fn json(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape a string for the inside of a quoted DOT identifier.
+/// Line breaks are written as `\n` and tabs as spaces, carriage returns are dropped
+/// and other control characters are replaced with U+FFFD, as DOT has no escape for them.
+pub(crate) fn dot(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push(' '),
+            '\r' => {}
+            c if (c as u32) < 0x20 || c == '\u{7f}' => escaped.push(char::REPLACEMENT_CHARACTER),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape a string for the inside of a quoted XML attribute.
+/// Tabs and line breaks are kept as character references, other control characters are not allowed
+/// in XML and are replaced with U+FFFD.
+pub(crate) fn xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\t' | '\n' | '\r' => {
+                let _ = write!(escaped, "&#{};", c as u32);
+            }
+            c if (c as u32) < 0x20 => escaped.push(char::REPLACEMENT_CHARACTER),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        assert_eq!(json("a \"b\" \\c"), "a \\\"b\\\" \\\\c");
+        assert_eq!(json("a\nb\tc\u{1}"), "a\\nb\\tc\\u0001");
+
+        let value = "q\"\\\n\r\t\u{0}\u{1f}é";
+        let parsed: String = serde_json::from_str(&format!("\"{}\"", json(value))).unwrap();
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn test_dot() {
+        assert_eq!(dot("a \"b\" \\c"), "a \\\"b\\\" \\\\c");
+        assert_eq!(dot("a\r\nb\tc\u{1}d\u{7f}"), "a\\nb c\u{fffd}d\u{fffd}");
+    }
+
+    #[test]
+    fn test_xml() {
+        assert_eq!(
+            xml("<a & 'b'>\"c\""),
+            "&lt;a &amp; &apos;b&apos;&gt;&quot;c&quot;"
+        );
+        assert_eq!(xml("a\nb\u{1}"), "a&#10;b\u{fffd}");
+    }
+}
@@ -1,15 +1,586 @@
-//! Support for the [`bevy`][::prelude][::Image] crate.
+//! Support for the [`bevy`][::bevy] crate.
+//!
+//! Implements [`Image`] for `bevy::prelude::Image` with an uncompressed texture format, and provides
+//! [`BillowPlugin`] to generate tilemaps at runtime: add a [`TilemapRequest`] to an entity, the grid
+//! is solved on the async compute task pool and its tiles are spawned as sprites, children of the entity.
+//! ```ignore
+//! use bevy::prelude::*;
+//! use billow::bevy::{BillowPlugin, TilemapFinished, TilemapRequest};
+//!
+//! fn setup(mut commands: Commands, assets: Res<AssetServer>) {
+//!     let tiles = ["blank.png", "up.png", "right.png", "down.png", "left.png"]
+//!         .map(|name| assets.load(&format!("basic/{}", name)));
+//!     commands
+//!         .spawn_bundle(SpatialBundle::default())
+//!         .insert(TilemapRequest::new(tiles.to_vec(), 20, 20).seed(42));
+//! }
+//!
+//! App::new()
+//!     .add_plugins(DefaultPlugins)
+//!     .add_plugin(BillowPlugin)
+//!     .add_startup_system(setup)
+//!     .run();
+//! ```
 
-use crate::Image;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
+use bevy::{
+    app::{App, Plugin},
+    asset::{Assets, Handle},
+    ecs::prelude::*,
+    hierarchy::BuildChildren,
+    math::Vec2,
+    render::render_resource::TextureFormat,
+    sprite::SpriteBundle,
+    tasks::{AsyncComputeTaskPool, Task},
+    transform::components::Transform,
+};
+
+use crate::{
+    pixel::{decode_channel, Channels, Depth, PixelFormat},
+    CollapseError, Image, Wave,
+};
+
+/// Converts the pixels of the uncompressed 8-bit, 16-bit and float texture formats.
+/// Other formats, e.g. compressed or signed textures, fail [`Image::format`] and read as transparent black.
 impl Image for bevy::prelude::Image {
     fn size(&self) -> (usize, usize) {
         (self.size().x as usize, self.size().y as usize)
     }
     fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+        let Some(texture) = texture_format(self.texture_descriptor.format) else {
+            return [0; 4];
+        };
+        let format = texture.format;
         let width = self.size().x as usize;
-        let idx = (x + y * width) * 4;
+        let idx = (x + y * width) * format.bytes_per_pixel();
+        let Some(bytes) = self.data.get(idx..idx + format.bytes_per_pixel()) else {
+            return [0; 4];
+        };
+
+        let mut values = [0; 4];
+        for (value, bytes) in values
+            .iter_mut()
+            .zip(bytes.chunks_exact(format.depth.bytes()))
+        {
+            *value = decode_channel(bytes, format.depth, texture.normalized);
+        }
+        let [r, g, b, a] = format.to_rgba(&values);
+        if texture.bgra {
+            [b, g, r, a]
+        } else {
+            [r, g, b, a]
+        }
+    }
+    fn format(&self) -> Result<PixelFormat, String> {
+        let format = self.texture_descriptor.format;
+        texture_format(format)
+            .map(|texture| texture.format)
+            .ok_or_else(|| format!("Unsupported texture format {:?}", format))
+    }
+}
+
+/// How the pixels of a texture are read.
+struct Texture {
+    format: PixelFormat,
+    /// Integers are scaled from their whole range, `Unorm` formats, instead of read as is, `Uint` formats.
+    normalized: bool,
+    /// Blue comes before red.
+    bgra: bool,
+}
+
+/// Returns how to read the pixels of a texture, `None` if the format is not supported.
+/// Single channel textures are read as gray, two channel ones as gray and alpha, like the textures
+/// loaded from grayscale images.
+fn texture_format(format: TextureFormat) -> Option<Texture> {
+    let (channels, depth, normalized) = match format {
+        TextureFormat::R8Unorm => (Channels::Gray, Depth::U8, true),
+        TextureFormat::R8Uint => (Channels::Gray, Depth::U8, false),
+        TextureFormat::Rg8Unorm => (Channels::GrayAlpha, Depth::U8, true),
+        TextureFormat::Rg8Uint => (Channels::GrayAlpha, Depth::U8, false),
+        TextureFormat::Rgba8Unorm
+        | TextureFormat::Rgba8UnormSrgb
+        | TextureFormat::Bgra8Unorm
+        | TextureFormat::Bgra8UnormSrgb => (Channels::Rgba, Depth::U8, true),
+        TextureFormat::Rgba8Uint => (Channels::Rgba, Depth::U8, false),
+        TextureFormat::R16Unorm => (Channels::Gray, Depth::U16, true),
+        TextureFormat::R16Uint => (Channels::Gray, Depth::U16, false),
+        TextureFormat::Rg16Unorm => (Channels::GrayAlpha, Depth::U16, true),
+        TextureFormat::Rg16Uint => (Channels::GrayAlpha, Depth::U16, false),
+        TextureFormat::Rgba16Unorm => (Channels::Rgba, Depth::U16, true),
+        TextureFormat::Rgba16Uint => (Channels::Rgba, Depth::U16, false),
+        TextureFormat::R16Float => (Channels::Gray, Depth::F16, true),
+        TextureFormat::Rg16Float => (Channels::GrayAlpha, Depth::F16, true),
+        TextureFormat::Rgba16Float => (Channels::Rgba, Depth::F16, true),
+        TextureFormat::R32Float => (Channels::Gray, Depth::F32, true),
+        TextureFormat::Rg32Float => (Channels::GrayAlpha, Depth::F32, true),
+        TextureFormat::Rgba32Float => (Channels::Rgba, Depth::F32, true),
+        _ => return None,
+    };
+    Some(Texture {
+        format: PixelFormat::new(channels, depth),
+        normalized,
+        bgra: matches!(
+            format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ),
+    })
+}
+
+/// Solves the [`TilemapRequest`]s and spawns their tiles.
+pub struct BillowPlugin;
+
+impl Plugin for BillowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TilemapProgress>()
+            .add_event::<TilemapContradiction>()
+            .add_event::<TilemapFinished>()
+            .add_event::<TilemapFailed>()
+            .add_system(start_generation)
+            .add_system(poll_generation);
+    }
+}
+
+/// Describes a tilemap to generate.
+///
+/// The generation starts once all the tiles are loaded. When it succeeds the request is replaced
+/// by a [`Tilemap`], and a sprite with a [`Tile`] is spawned as a child for each slot.
+#[derive(Component, Debug, Clone)]
+pub struct TilemapRequest {
+    /// Images of the tiles, in the order of the modules.
+    pub tiles: Vec<Handle<bevy::prelude::Image>>,
+    /// Width of the grid.
+    pub width: usize,
+    /// Height of the grid.
+    pub height: usize,
+    /// Seed of the generation, random if not set.
+    pub seed: Option<u64>,
+    /// Size of a tile in world units, the size of the first image if not set.
+    pub tile_size: Option<Vec2>,
+    /// Number of times the generation starts over after a contradiction.
+    pub attempts: usize,
+    /// How long the generation can run, over all the attempts, without limit if not set.
+    pub timeout: Option<Duration>,
+}
+
+impl TilemapRequest {
+    /// Create a request for a grid of `width` by `height` tiles.
+    pub fn new(tiles: Vec<Handle<bevy::prelude::Image>>, width: usize, height: usize) -> Self {
+        Self {
+            tiles,
+            width,
+            height,
+            seed: None,
+            tile_size: None,
+            attempts: 10,
+            timeout: None,
+        }
+    }
+
+    /// Set the seed of the generation.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set the size of a tile in world units.
+    pub fn tile_size(mut self, tile_size: Vec2) -> Self {
+        self.tile_size = Some(tile_size);
+        self
+    }
+
+    /// Set the number of times the generation starts over after a contradiction.
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Set how long the generation can run before it fails.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// A generated tilemap, with the module of every slot.
+#[derive(Component, Debug, Clone)]
+pub struct Tilemap {
+    pub width: usize,
+    pub height: usize,
+    /// Module of each slot, row by row.
+    pub modules: Vec<usize>,
+}
+
+/// A tile of a generated tilemap.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub module: usize,
+}
+
+/// Sent while a tilemap is generated.
+#[derive(Debug, Clone)]
+pub struct TilemapProgress {
+    pub entity: Entity,
+    /// Number of collapsed slots.
+    pub collapsed: usize,
+    /// Number of slots of the grid.
+    pub total: usize,
+}
+
+/// Sent when the generation of a tilemap reaches a contradiction and starts over.
+#[derive(Debug, Clone)]
+pub struct TilemapContradiction {
+    pub entity: Entity,
+    /// Number of the attempt that failed, from 0.
+    pub attempt: usize,
+    pub reason: String,
+}
+
+/// Sent when the tiles of a tilemap are spawned.
+#[derive(Debug, Clone)]
+pub struct TilemapFinished {
+    pub entity: Entity,
+}
+
+/// Sent when all the attempts to generate a tilemap failed or it timed out. The request is removed.
+#[derive(Debug, Clone)]
+pub struct TilemapFailed {
+    pub entity: Entity,
+    pub reason: String,
+}
+
+/// Messages sent by the generation task.
+enum Message {
+    Progress(usize),
+    Contradiction(usize, String),
+    Finished(Vec<usize>),
+    Failed(String),
+}
+
+/// A generation running on the async compute task pool.
+#[derive(Component)]
+struct Generation {
+    receiver: Mutex<Receiver<Message>>,
+    /// Stops the collapse when the generation is removed, e.g. with its entity.
+    cancel: Arc<AtomicBool>,
+    // The task never yields, so dropping it does not stop a collapse that already started.
+    _task: Task<()>,
+}
+
+impl Drop for Generation {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Start the requests whose tiles are loaded.
+fn start_generation(
+    mut commands: Commands,
+    images: Res<Assets<bevy::prelude::Image>>,
+    requests: Query<(Entity, &TilemapRequest), Without<Generation>>,
+) {
+    for (entity, request) in requests.iter() {
+        let Some(tiles) = request
+            .tiles
+            .iter()
+            .map(|handle| images.get(handle).cloned())
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        let (width, height, seed) = (request.width, request.height, request.seed);
+        let (attempts, timeout) = (request.attempts, request.timeout);
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let token = cancel.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            // Sampling the edges of the tiles reads every image, keep it off the main thread.
+            match Wave::try_new(&tiles, width, height) {
+                Ok(mut wave) => {
+                    if let Some(seed) = seed {
+                        wave.set_seed(seed);
+                    }
+                    wave.set_cancel_token(token);
+                    wave.set_deadline(deadline);
+                    generate(wave, attempts, sender);
+                }
+                Err(reason) => {
+                    let _ = sender.send(Message::Failed(reason));
+                }
+            }
+        });
+        commands.entity(entity).insert(Generation {
+            receiver: Mutex::new(receiver),
+            cancel,
+            _task: task,
+        });
+    }
+}
+
+/// Solve the wave, starting over after each contradiction.
+fn generate(mut wave: Wave, attempts: usize, sender: Sender<Message>) {
+    let total = wave.width() * wave.height();
+    for attempt in 0..attempts.max(1) {
+        wave.initialize();
+        let mut percent = 0;
+        let result = wave.collapse_with(total as i32, |wave| {
+            let collapsed = wave
+                .grid
+                .iter()
+                .filter(|slot| slot.superposition.len() == 1)
+                .count();
+            // Report each percent, sending every slot would flood the channel.
+            if collapsed * 100 / total.max(1) > percent {
+                percent = collapsed * 100 / total.max(1);
+                let _ = sender.send(Message::Progress(collapsed));
+            }
+        });
+        match result {
+            Ok(()) if wave.is_collapsed() => {
+                let modules = wave
+                    .grid
+                    .iter()
+                    .map(|slot| slot.superposition[0].index)
+                    .collect();
+                let _ = sender.send(Message::Finished(modules));
+                return;
+            }
+            Ok(()) => {
+                let reason = String::from("The wave did not collapse");
+                let _ = sender.send(Message::Contradiction(attempt, reason));
+            }
+            // Nobody listens anymore.
+            Err(CollapseError::Cancelled) => return,
+            Err(CollapseError::TimedOut) => {
+                let reason = format!("Timed out during attempt {}", attempt);
+                let _ = sender.send(Message::Failed(reason));
+                return;
+            }
+            Err(err) => {
+                let _ = sender.send(Message::Contradiction(attempt, err.to_string()));
+            }
+        }
+    }
+    let reason = format!("No solution after {} attempts", attempts.max(1));
+    let _ = sender.send(Message::Failed(reason));
+}
+
+/// Forward the messages of the running generations as events, and spawn the finished tilemaps.
+fn poll_generation(
+    mut commands: Commands,
+    images: Res<Assets<bevy::prelude::Image>>,
+    generations: Query<(Entity, &TilemapRequest, &Generation)>,
+    mut progress: EventWriter<TilemapProgress>,
+    mut contradictions: EventWriter<TilemapContradiction>,
+    mut finished: EventWriter<TilemapFinished>,
+    mut failed: EventWriter<TilemapFailed>,
+) {
+    for (entity, request, generation) in generations.iter() {
+        let receiver = generation.receiver.lock().expect("Generation poisoned");
+        for message in receiver.try_iter() {
+            match message {
+                Message::Progress(collapsed) => progress.send(TilemapProgress {
+                    entity,
+                    collapsed,
+                    total: request.width * request.height,
+                }),
+                Message::Contradiction(attempt, reason) => {
+                    contradictions.send(TilemapContradiction {
+                        entity,
+                        attempt,
+                        reason,
+                    })
+                }
+                Message::Finished(modules) => {
+                    spawn_tiles(&mut commands, &images, entity, request, &modules);
+                    commands
+                        .entity(entity)
+                        .remove::<Generation>()
+                        .remove::<TilemapRequest>()
+                        .insert(Tilemap {
+                            width: request.width,
+                            height: request.height,
+                            modules,
+                        });
+                    finished.send(TilemapFinished { entity });
+                }
+                Message::Failed(reason) => {
+                    commands
+                        .entity(entity)
+                        .remove::<Generation>()
+                        .remove::<TilemapRequest>();
+                    failed.send(TilemapFailed { entity, reason });
+                }
+            }
+        }
+    }
+}
+
+/// Spawn a sprite for each slot, the first row at the top.
+fn spawn_tiles(
+    commands: &mut Commands,
+    images: &Assets<bevy::prelude::Image>,
+    entity: Entity,
+    request: &TilemapRequest,
+    modules: &[usize],
+) {
+    let tile_size = request.tile_size.unwrap_or_else(|| {
+        request
+            .tiles
+            .first()
+            .and_then(|handle| images.get(handle))
+            .map(|image| image.size())
+            .unwrap_or(Vec2::ONE)
+    });
+    commands.entity(entity).with_children(|parent| {
+        for (idx, module) in modules.iter().enumerate() {
+            let (x, y) = (idx % request.width, idx / request.width);
+            parent
+                .spawn_bundle(SpriteBundle {
+                    texture: request.tiles[*module].clone(),
+                    transform: Transform::from_xyz(
+                        x as f32 * tile_size.x,
+                        -(y as f32) * tile_size.y,
+                        0.0,
+                    ),
+                    ..Default::default()
+                })
+                .insert(Tile {
+                    x,
+                    y,
+                    module: *module,
+                });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use bevy::{
+        asset::{AddAsset, AssetPlugin},
+        ecs::event::{Events, ManualEventReader},
+        hierarchy::Children,
+        render::render_resource::{Extent3d, TextureDimension},
+        MinimalPlugins,
+    };
+
+    use super::*;
+
+    /// 3x3 texture of a single color.
+    fn texture(color: [u8; 4]) -> bevy::prelude::Image {
+        bevy::prelude::Image::new(
+            Extent3d {
+                width: 3,
+                height: 3,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            color.repeat(9),
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    #[test]
+    fn test_plugin() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<bevy::prelude::Image>()
+            .add_plugin(BillowPlugin);
+
+        let mut images = app.world.resource_mut::<Assets<bevy::prelude::Image>>();
+        let tiles = vec![
+            images.add(texture([0, 0, 0, 255])),
+            images.add(texture([0, 0, 0, 255])),
+        ];
+        let entity = app
+            .world
+            .spawn()
+            .insert(TilemapRequest::new(tiles, 4, 3).seed(1))
+            .id();
+
+        // The generation runs on another thread, give it up to 10 seconds.
+        let mut reader = ManualEventReader::<TilemapFinished>::default();
+        let mut finished = vec![];
+        for _ in 0..1000 {
+            app.update();
+            let events = app.world.resource::<Events<TilemapFinished>>();
+            finished.extend(reader.iter(events).map(|event| event.entity));
+            if !finished.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(finished, [entity]);
+        assert!(app.world.get::<TilemapRequest>(entity).is_none());
+        let tilemap = app.world.get::<Tilemap>(entity).expect("No tilemap");
+        assert_eq!(tilemap.modules.len(), 12);
+        let children = app.world.get::<Children>(entity).expect("No tiles");
+        assert_eq!(children.len(), 12);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<bevy::prelude::Image>()
+            .add_plugin(BillowPlugin);
+
+        let mut images = app.world.resource_mut::<Assets<bevy::prelude::Image>>();
+        let tiles = vec![images.add(texture([0, 0, 0, 255]))];
+        let request = TilemapRequest::new(tiles, 20, 20).timeout(Duration::ZERO);
+        let entity = app.world.spawn().insert(request).id();
+
+        let mut reader = ManualEventReader::<TilemapFailed>::default();
+        let mut failed = vec![];
+        for _ in 0..1000 {
+            app.update();
+            let events = app.world.resource::<Events<TilemapFailed>>();
+            failed.extend(reader.iter(events).map(|event| event.entity));
+            if !failed.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(failed, [entity]);
+        assert!(app.world.get::<TilemapRequest>(entity).is_none());
+        assert!(app.world.get::<Generation>(entity).is_none());
+    }
+
+    #[test]
+    fn test_formats() {
+        let mut image = texture([10, 20, 30, 40]);
+        assert_eq!(image.get_pixel_at(2, 2), [10, 20, 30, 40]);
+        image.texture_descriptor.format = TextureFormat::Bgra8UnormSrgb;
+        assert_eq!(image.get_pixel_at(0, 0), [30, 20, 10, 40]);
+
+        // Two 16-bit channels per pixel.
+        image.texture_descriptor.format = TextureFormat::Rg16Uint;
+        image.data = [300u16, 7].iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(image.get_pixel_at(0, 0), [255, 255, 255, 7]);
+        image.texture_descriptor.format = TextureFormat::Rg16Unorm;
+        assert_eq!(image.get_pixel_at(0, 0), [1, 1, 1, 0]);
+        assert_eq!(image.get_pixel_at(2, 2), [0; 4]);
 
-        self.data[idx..=idx + 3].try_into().unwrap()
+        image.texture_descriptor.format = TextureFormat::Bc1RgbaUnorm;
+        assert!(image.format().is_err());
+        assert_eq!(image.get_pixel_at(0, 0), [0; 4]);
+        assert!(Wave::try_new(&[image], 2, 2).is_err());
     }
 }
@@ -1,7 +1,15 @@
 //! Support for the [`image`][::image] crate.
+//!
+//! Implements [`Image`] for `DynamicImage` and for the `ImageBuffer`s of `Luma`, `LumaA`, `Rgb`
+//! and `Rgba` pixels, with 8-bit, 16-bit or float channels.
 
-use crate::Image;
-use image::{DynamicImage, GenericImageView};
+use std::ops::Deref;
+
+use crate::{
+    pixel::{Channel, Channels, Depth, PixelFormat},
+    Image,
+};
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, PixelWithColorType};
 
 impl Image for DynamicImage {
     fn size(&self) -> (usize, usize) {
@@ -11,4 +19,84 @@ impl Image for DynamicImage {
         let pixel = self.get_pixel(x as u32, y as u32);
         [pixel[0], pixel[1], pixel[2], pixel[3]]
     }
+    fn format(&self) -> Result<PixelFormat, String> {
+        Ok(self.color().into())
+    }
+}
+
+impl<P, C> Image for ImageBuffer<P, C>
+where
+    P: PixelWithColorType,
+    P::Subpixel: Channel,
+    C: Deref<Target = [P::Subpixel]>,
+{
+    fn size(&self) -> (usize, usize) {
+        (self.width() as usize, self.height() as usize)
+    }
+    fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+        self.get_pixel(x as u32, y as u32)
+            .to_rgba()
+            .0
+            .map(Channel::to_u8)
+    }
+    fn format(&self) -> Result<PixelFormat, String> {
+        Ok(P::COLOR_TYPE.into())
+    }
+}
+
+impl From<ColorType> for PixelFormat {
+    fn from(color: ColorType) -> Self {
+        let channels = match color.channel_count() {
+            1 => Channels::Gray,
+            2 => Channels::GrayAlpha,
+            3 => Channels::Rgb,
+            _ => Channels::Rgba,
+        };
+        let depth = match color {
+            ColorType::Rgb32F | ColorType::Rgba32F => Depth::F32,
+            _ if color.bytes_per_pixel() > color.channel_count() => Depth::U16,
+            _ => Depth::U8,
+        };
+        PixelFormat::new(channels, depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayAlphaImage, ImageBuffer, Luma, Rgb, Rgb32FImage};
+
+    #[test]
+    fn test_buffers() {
+        let gray: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_raw(2, 1, vec![0, 0x8080]).unwrap();
+        assert_eq!(gray.get_pixel_at(1, 0), [128, 128, 128, 255]);
+        assert_eq!(
+            gray.format(),
+            Ok(PixelFormat::new(Channels::Gray, Depth::U16))
+        );
+
+        let alpha = GrayAlphaImage::from_raw(1, 1, vec![7, 9]).unwrap();
+        assert_eq!(alpha.get_pixel_at(0, 0), [7, 7, 7, 9]);
+
+        let float = Rgb32FImage::from_pixel(1, 1, Rgb([1.0, 0.5, 0.0]));
+        assert_eq!(float.get_pixel_at(0, 0), [255, 128, 0, 255]);
+        assert_eq!(
+            float.format(),
+            Ok(PixelFormat::new(Channels::Rgb, Depth::F32))
+        );
+    }
+
+    #[test]
+    fn test_dynamic() {
+        let gray: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_raw(2, 1, vec![0, 0x8080]).unwrap();
+        let dynamic = DynamicImage::ImageLuma16(gray.clone());
+        assert_eq!(dynamic.format(), gray.format());
+        assert_eq!(dynamic.get_pixel_at(1, 0), gray.get_pixel_at(1, 0));
+        assert_eq!(
+            DynamicImage::new_rgba8(1, 1).format(),
+            Ok(PixelFormat::RGBA8)
+        );
+    }
 }
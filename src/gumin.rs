@@ -0,0 +1,356 @@
+//! Import of the `data.xml` tilesets of the original [WaveFunctionCollapse](https://github.com/mxgmn/WaveFunctionCollapse).
+//!
+//! ```xml
+//! <set>
+//!     <tiles>
+//!         <tile name="empty" symmetry="X"/>
+//!         <tile name="t" symmetry="T" weight="0.5"/>
+//!     </tiles>
+//!     <neighbors>
+//!         <neighbor left="t" right="t 1"/>
+//!     </neighbors>
+//! </set>
+//! ```
+//!
+//! Images are read from `<name>.png` next to the file, or from `<name> <variant>.png` when the set is `unique`.
+//! Every neighbor rule is expanded to the rotated and reflected variants of the tiles, like the original does.
+//! Subsets are ignored.
+//! The neighbor rules are not saved with a wave, attach them again with `Tileset::restore` after loading it.
+
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
+
+use crate::{
+    direction::Direction,
+    tileset::{NeighborRule, TileDefinition, Tileset, TilesetDefinition, TilesetError},
+};
+
+/// Maps a variant to another one.
+type Action = fn(usize) -> usize;
+
+/// Number of variants, rotation and reflection of a symmetry, as numbered by the original.
+/// Rotations are counterclockwise.
+fn actions(symmetry: &str) -> Option<(usize, Action, Action)> {
+    match symmetry {
+        "L" => Some((
+            4,
+            |i| (i + 1) % 4,
+            |i| if i % 2 == 0 { i + 1 } else { i - 1 },
+        )),
+        "T" => Some((4, |i| (i + 1) % 4, |i| if i % 2 == 0 { i } else { 4 - i })),
+        "I" => Some((2, |i| 1 - i, |i| i)),
+        "\\" => Some((2, |i| 1 - i, |i| 1 - i)),
+        "F" => Some((
+            8,
+            |i| if i < 4 { (i + 1) % 4 } else { 4 + (i - 1) % 4 },
+            |i| if i < 4 { i + 4 } else { i - 4 },
+        )),
+        "X" => Some((1, |i| i, |i| i)),
+        _ => None,
+    }
+}
+
+/// Returns the name of the module for a variant numbered by the original.
+fn variant_name(name: &str, symmetry: &str, variant: usize, unique: bool) -> String {
+    if unique {
+        return format!("{} {}", name, variant);
+    }
+
+    // The original rotates counterclockwise, the tileset clockwise.
+    let variant = match symmetry {
+        "L" | "T" => (4 - variant) % 4,
+        "F" if variant < 4 => (4 - variant) % 4,
+        _ => variant,
+    };
+    if variant == 0 {
+        name.to_string()
+    } else {
+        format!("{} {}", name, variant)
+    }
+}
+
+impl TilesetDefinition {
+    /// Convert the contents of a `data.xml` file.
+    pub fn from_gumin(xml: &str) -> Result<Self, TilesetError> {
+        let document =
+            roxmltree::Document::parse(xml).map_err(|e| TilesetError::Parse(e.to_string()))?;
+        let set = document.root_element();
+        let unique = set
+            .attribute("unique")
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+
+        // The rules are expanded here, where the variants of unique sets are known.
+        let mut definition = TilesetDefinition {
+            exact_neighbors: true,
+            ..Default::default()
+        };
+        // Index of the first variant and number of variants of each tile.
+        let mut first = HashMap::new();
+        // Variants after each rotation and reflection.
+        let mut action: Vec<[usize; 8]> = vec![];
+        let mut names = vec![];
+
+        for node in set.descendants().filter(|n| n.has_tag_name("tile")) {
+            let name = node
+                .attribute("name")
+                .ok_or_else(|| TilesetError::Parse(String::from("tile without name")))?;
+            let symmetry = node.attribute("symmetry").unwrap_or("X");
+            let weight = match node.attribute("weight") {
+                Some(weight) => weight
+                    .parse()
+                    .map_err(|_| TilesetError::tile(name, format!("invalid weight {}", weight)))?,
+                None => 1.0,
+            };
+            let (cardinality, a, b) = actions(symmetry).ok_or_else(|| {
+                TilesetError::tile(name, format!("unknown symmetry {}", symmetry))
+            })?;
+
+            let offset = action.len();
+            first.insert(name.to_string(), (offset, cardinality));
+            for t in 0..cardinality {
+                let map = [
+                    t,
+                    a(t),
+                    a(a(t)),
+                    a(a(a(t))),
+                    b(t),
+                    b(a(t)),
+                    b(a(a(t))),
+                    b(a(a(a(t)))),
+                ];
+                action.push(map.map(|variant| variant + offset));
+                names.push(variant_name(name, symmetry, t, unique));
+            }
+
+            let tile = |name: String, image: String, symmetry: &str| TileDefinition {
+                name,
+                image: Some(PathBuf::from(image)),
+                symmetry: symmetry.to_string(),
+                weight,
+                connectors: Default::default(),
+            };
+            if unique {
+                for t in 0..cardinality {
+                    let variant = format!("{} {}", name, t);
+                    definition
+                        .tiles
+                        .push(tile(variant.clone(), format!("{}.png", variant), "X"));
+                }
+            } else {
+                definition
+                    .tiles
+                    .push(tile(name.to_string(), format!("{}.png", name), symmetry));
+            }
+        }
+
+        for node in set.descendants().filter(|n| n.has_tag_name("neighbor")) {
+            let variant = |side: &str| -> Result<usize, TilesetError> {
+                let value = node.attribute(side).ok_or_else(|| {
+                    TilesetError::Parse(format!("neighbor without {} tile", side))
+                })?;
+                let (name, variant) = match value.split_once(' ') {
+                    Some((name, variant)) => (name, variant.parse().ok()),
+                    None => (value, Some(0)),
+                };
+                match (first.get(name), variant) {
+                    (Some(&(offset, cardinality)), Some(variant)) if variant < cardinality => {
+                        Ok(offset + variant)
+                    }
+                    _ => Err(TilesetError::tile(value, "unknown tile in neighbor rule")),
+                }
+            };
+
+            let (l, r) = (variant("left")?, variant("right")?);
+            let (d, u) = (action[l][1], action[r][1]);
+
+            let horizontal = [
+                (l, r),
+                (action[l][6], action[r][6]),
+                (action[r][4], action[l][4]),
+                (action[r][2], action[l][2]),
+            ];
+            let vertical = [
+                (u, d),
+                (action[d][6], action[u][6]),
+                (action[u][4], action[d][4]),
+                (action[d][2], action[u][2]),
+            ];
+            let rules = horizontal
+                .iter()
+                .map(|pair| (pair, Direction::Right))
+                .chain(vertical.iter().map(|pair| (pair, Direction::Down)));
+            for (&(tile, neighbor), direction) in rules {
+                definition.neighbors.push(NeighborRule {
+                    tile: names[tile].clone(),
+                    direction,
+                    neighbor: names[neighbor].clone(),
+                });
+            }
+        }
+
+        Ok(definition)
+    }
+}
+
+impl Tileset {
+    /// Load a `data.xml` file and the images next to it.
+    pub fn load_gumin(path: impl AsRef<Path>) -> Result<Self, TilesetError> {
+        let path = path.as_ref();
+        let xml = fs::read_to_string(path).map_err(TilesetError::Io)?;
+        let definition = TilesetDefinition::from_gumin(&xml)?;
+        Tileset::from_definition(definition, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_variant_name() {
+        assert_eq!(variant_name("t", "T", 0, false), "t");
+        assert_eq!(variant_name("t", "T", 1, false), "t 3");
+        assert_eq!(variant_name("t", "T", 1, true), "t 1");
+        assert_eq!(variant_name("f", "F", 5, false), "f 5");
+        assert_eq!(variant_name("line", "I", 1, false), "line 1");
+    }
+
+    #[test]
+    fn test_from_gumin() {
+        let xml = r#"
+            <set>
+                <tiles>
+                    <tile name="empty"/>
+                    <tile name="line" symmetry="I" weight="2"/>
+                </tiles>
+                <neighbors>
+                    <neighbor left="line" right="line"/>
+                    <neighbor left="empty" right="line 1"/>
+                </neighbors>
+            </set>"#;
+        let definition = TilesetDefinition::from_gumin(xml).unwrap();
+
+        assert_eq!(definition.tiles.len(), 2);
+        assert_eq!(definition.tiles[1].weight, 2.0);
+        assert_eq!(definition.tiles[1].image, Some(PathBuf::from("line.png")));
+
+        let rule = |tile: &str, direction, neighbor: &str| NeighborRule {
+            tile: tile.to_string(),
+            direction,
+            neighbor: neighbor.to_string(),
+        };
+        assert!(definition
+            .neighbors
+            .contains(&rule("line", Direction::Right, "line")));
+        assert!(definition
+            .neighbors
+            .contains(&rule("line 1", Direction::Down, "line 1")));
+        assert!(definition
+            .neighbors
+            .contains(&rule("empty", Direction::Down, "line")));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |xml: &str| {
+            TilesetDefinition::from_gumin(xml)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            error(r#"<set><tiles><tile name="a" symmetry="Q"/></tiles></set>"#),
+            "Tile \"a\": unknown symmetry Q"
+        );
+        assert_eq!(
+            error(r#"<set><neighbors><neighbor left="a" right="b"/></neighbors></set>"#),
+            "Tile \"a\": unknown tile in neighbor rule"
+        );
+        assert!(error("<set>").starts_with("Invalid tileset"));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_propagate() {
+        // Seeds that ended with mismatched neighbors when propagation skipped the slots it came from.
+        let tileset = Tileset::load_gumin("assets/knots/data.xml").unwrap();
+        for seed in 0..70 {
+            let mut wave = tileset.wave(6, 6);
+            wave.set_seed(seed);
+            wave.initialize();
+            if testing::run(&mut wave).is_ok() {
+                assert!(
+                    testing::is_valid(&wave),
+                    "Seed {} has mismatched neighbors",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_restore() {
+        let tileset = Tileset::load_gumin("assets/knots/data.xml").unwrap();
+        let indices = |wave: &crate::Wave| -> Vec<Vec<usize>> {
+            wave.grid
+                .iter()
+                .map(|slot| slot.superposition.iter().map(|m| m.index).collect())
+                .collect()
+        };
+        for seed in 0..10 {
+            let mut wave = tileset.wave(6, 6);
+            wave.set_seed(seed);
+            wave.initialize();
+            if wave.collapse(5).is_err() {
+                continue;
+            }
+
+            let saved = serde_json::to_string(&wave).unwrap();
+            let mut resumed: crate::Wave = serde_json::from_str(&saved).unwrap();
+            tileset.restore(&mut resumed);
+
+            let result = testing::run(&mut wave);
+            assert_eq!(result, testing::run(&mut resumed));
+            assert_eq!(indices(&wave), indices(&resumed));
+            if result.is_ok() {
+                assert!(testing::is_valid(&resumed), "Seed {} is not valid", seed);
+            }
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_load_gumin() {
+        use crate::get_constraint_fn;
+
+        let tileset = Tileset::load_gumin("assets/knots/data.xml").unwrap();
+        let mut wave = tileset.wave(6, 6);
+        testing::solve(&mut wave, 100);
+
+        // The expanded rules only allow neighbors whose edges match.
+        let images: Vec<_> = (0..tileset.len())
+            .map(|idx| image::DynamicImage::ImageRgba8(tileset.image(idx).unwrap()))
+            .collect();
+        let constraint_fn = get_constraint_fn(3);
+        for slot in wave.grid.iter().filter(|slot| slot.x < 5) {
+            let right = &wave.grid[slot.y * 6 + slot.x + 1];
+            let a = &images[slot.superposition[0].index];
+            let b = &images[right.superposition[0].index];
+            assert_eq!(
+                constraint_fn(a, Direction::Right),
+                constraint_fn(b, Direction::Left)
+            );
+        }
+        for slot in wave.grid.iter().filter(|slot| slot.y < 5) {
+            let below = &wave.grid[(slot.y + 1) * 6 + slot.x];
+            let a = &images[slot.superposition[0].index];
+            let b = &images[below.superposition[0].index];
+            assert_eq!(
+                constraint_fn(a, Direction::Down),
+                constraint_fn(b, Direction::Up)
+            );
+        }
+    }
+}
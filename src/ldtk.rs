@@ -0,0 +1,328 @@
+//! Export of a collapsed wave as an [LDtk](https://ldtk.io) project.
+//!
+//! The project has a single level with two layers:
+//! - `Modules`, an IntGrid layer where the value of a cell is the module index plus one.
+//! - `Tiles`, a tile layer drawn from an atlas image with one tile per module, row by row.
+//!
+//! Slots that are not collapsed are left empty.
+
+use std::fmt::Write;
+
+use crate::{escape, wave::Wave};
+
+/// Version of the LDtk file format that is written.
+const JSON_VERSION: &str = "1.5.3";
+
+const TILESET_UID: usize = 1;
+const INT_GRID_UID: usize = 2;
+const TILES_UID: usize = 3;
+
+/// Returns a unique instance identifier. LDtk only requires them to be unique in a project.
+fn iid(uid: usize) -> String {
+    format!("00000000-0000-0000-0000-{:012x}", uid)
+}
+
+/// Turn module names into LDtk identifiers, made of letters, digits and underscores and not starting
+/// with a digit. Other characters become underscores, e.g. `"t 1"` is `t_1`, and a suffix keeps them unique.
+fn identifiers(names: &[String]) -> Vec<String> {
+    let mut identifiers: Vec<String> = Vec::with_capacity(names.len());
+    for name in names {
+        let mut identifier: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if !identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            identifier.insert(0, '_');
+        }
+        let base = identifier.clone();
+        let mut count = 1;
+        while identifiers.contains(&identifier) {
+            count += 1;
+            identifier = format!("{}_{}", base, count);
+        }
+        identifiers.push(identifier);
+    }
+    identifiers
+}
+
+/// Returns a color for an IntGrid value, spread around the hue circle.
+fn color(value: usize) -> String {
+    let hue = (value * 137) % 360;
+    let x = (255 * (60 - (hue % 120).abs_diff(60)) / 60) as u8;
+    let [r, g, b] = match hue / 60 {
+        0 => [255, x, 0],
+        1 => [x, 255, 0],
+        2 => [0, 255, x],
+        3 => [0, x, 255],
+        4 => [x, 0, 255],
+        _ => [255, 0, x],
+    };
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+/// Describes how the modules of a wave are drawn in LDtk.
+/// ```
+/// use billow::LdtkProject;
+///
+/// // `atlas.png` holds the 16x16 tiles of the modules, 8 per row.
+/// let project = LdtkProject::new("atlas.png", 8, vec!["blank".into(), "down".into()], 16);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LdtkProject {
+    /// Path of the atlas image, relative to the project file.
+    pub atlas: String,
+    /// Number of tiles in a row of the atlas.
+    pub columns: usize,
+    /// Name of each module, used as identifier of the IntGrid values once the characters LDtk
+    /// does not allow are replaced.
+    pub names: Vec<String>,
+    /// Size of the square tiles in pixels.
+    pub tile_size: usize,
+}
+
+impl LdtkProject {
+    /// Create a project where module `i` is the `i`th tile of the atlas.
+    pub fn new(
+        atlas: impl Into<String>,
+        columns: usize,
+        names: Vec<String>,
+        tile_size: usize,
+    ) -> Self {
+        Self {
+            atlas: atlas.into(),
+            columns: columns.max(1),
+            names,
+            tile_size,
+        }
+    }
+
+    /// Create a project for the modules of a tileset.
+    /// The atlas can be created with [`Tileset::atlas`](crate::Tileset::atlas).
+    #[cfg(feature = "tileset")]
+    pub fn from_tileset<T>(
+        tileset: &crate::tileset::Tileset<T>,
+        atlas: impl Into<String>,
+        columns: usize,
+        tile_size: usize,
+    ) -> Self {
+        Self::new(atlas, columns, tileset.names.clone(), tile_size)
+    }
+
+    /// Returns the IntGrid value of every slot, row by row. `0` for slots that are not collapsed.
+    pub fn int_grid<T>(&self, wave: &Wave<T>) -> Vec<usize> {
+        wave.grid
+            .iter()
+            .map(|slot| match slot.superposition.as_slice() {
+                [module] if module.index < self.names.len() => module.index + 1,
+                _ => 0,
+            })
+            .collect()
+    }
+
+    /// Write the wave as an LDtk project with a single level.
+    pub fn to_json<T>(&self, wave: &Wave<T>) -> String {
+        let s = self.tile_size;
+        let (width, height) = (wave.width(), wave.height());
+        let rows = self.names.len().div_ceil(self.columns);
+        let atlas = escape::json(&self.atlas);
+        let int_grid = self.int_grid(wave);
+
+        let values: Vec<String> = identifiers(&self.names)
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                format!(
+                    "{{\"value\":{},\"identifier\":\"{}\",\"color\":\"{}\",\"tile\":null,\"groupUid\":0}}",
+                    idx + 1,
+                    name,
+                    color(idx)
+                )
+            })
+            .collect();
+
+        let mut tiles = vec![];
+        for (idx, &value) in int_grid.iter().enumerate() {
+            if value == 0 {
+                continue;
+            }
+            let tile = value - 1;
+            let (x, y) = (idx % width.max(1), idx / width.max(1));
+            let (sx, sy) = (tile % self.columns, tile / self.columns);
+            tiles.push(format!(
+                "{{\"px\":[{},{}],\"src\":[{},{}],\"f\":0,\"t\":{},\"d\":[{}],\"a\":1}}",
+                x * s,
+                y * s,
+                sx * s,
+                sy * s,
+                tile,
+                idx
+            ));
+        }
+
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            concat!(
+                "{{\"__header__\":{{\"fileType\":\"LDtk Project JSON\",\"app\":\"LDtk\",\"doc\":\"https://ldtk.io/json\",",
+                "\"schema\":\"https://ldtk.io/files/JSON_SCHEMA.json\",\"appAuthor\":\"Sebastien 'deepnight' Benard\",",
+                "\"appVersion\":\"{version}\",\"url\":\"https://ldtk.io\"}},",
+                "\"iid\":\"{iid}\",\"jsonVersion\":\"{version}\",\"appBuildId\":0,\"nextUid\":4,",
+                "\"identifierStyle\":\"Capitalize\",\"toc\":[],\"worldLayout\":\"Free\",",
+                "\"worldGridWidth\":{pw},\"worldGridHeight\":{ph},\"defaultLevelWidth\":{pw},\"defaultLevelHeight\":{ph},",
+                "\"defaultPivotX\":0,\"defaultPivotY\":0,\"defaultGridSize\":{s},\"defaultEntityWidth\":{s},\"defaultEntityHeight\":{s},",
+                "\"bgColor\":\"#40465B\",\"defaultLevelBgColor\":\"#696A79\",\"minifyJson\":false,\"externalLevels\":false,",
+                "\"exportTiled\":false,\"simplifiedExport\":false,\"imageExportMode\":\"None\",\"exportLevelBg\":true,",
+                "\"pngFilePattern\":null,\"backupOnSave\":false,\"backupLimit\":10,\"backupRelPath\":null,",
+                "\"levelNamePattern\":\"Level_%idx\",\"tutorialDesc\":null,\"customCommands\":[],\"flags\":[],",
+                "\"worlds\":[],\"dummyWorldIid\":\"{world}\",",
+            ),
+            version = JSON_VERSION,
+            iid = iid(0),
+            world = iid(1),
+            pw = width * s,
+            ph = height * s,
+            s = s,
+        );
+
+        // Definitions.
+        let layer_def = |kind: &str, uid: usize, values: &str, tileset: &str| {
+            format!(
+                concat!(
+                    "{{\"__type\":\"{kind}\",\"identifier\":\"{name}\",\"type\":\"{kind}\",\"uid\":{uid},\"doc\":null,",
+                    "\"uiColor\":null,\"gridSize\":{s},\"guideGridWid\":0,\"guideGridHei\":0,\"displayOpacity\":1,",
+                    "\"inactiveOpacity\":1,\"hideInList\":false,\"hideFieldsWhenInactive\":true,\"canSelectWhenInactive\":true,",
+                    "\"renderInWorldView\":true,\"pxOffsetX\":0,\"pxOffsetY\":0,\"parallaxFactorX\":0,\"parallaxFactorY\":0,",
+                    "\"parallaxScaling\":true,\"requiredTags\":[],\"excludedTags\":[],\"autoTilesKilledByOtherLayers\":false,",
+                    "\"uiFilterTags\":[],\"useAsyncRender\":false,\"intGridValues\":[{values}],\"intGridValuesGroups\":[],",
+                    "\"autoRuleGroups\":[],\"autoSourceLayerDefUid\":null,\"tilesetDefUid\":{tileset},",
+                    "\"tilePivotX\":0,\"tilePivotY\":0,\"biomeFieldUid\":null}}"
+                ),
+                kind = kind,
+                name = if kind == "IntGrid" { "Modules" } else { kind },
+                uid = uid,
+                s = s,
+                values = values,
+                tileset = tileset,
+            )
+        };
+        let _ = write!(
+            json,
+            concat!(
+                "\"defs\":{{\"layers\":[{int_grid},{tiles}],\"entities\":[],",
+                "\"tilesets\":[{{\"__cWid\":{columns},\"__cHei\":{rows},\"identifier\":\"Modules\",\"uid\":{uid},",
+                "\"relPath\":\"{atlas}\",\"embedAtlas\":null,\"pxWid\":{aw},\"pxHei\":{ah},\"tileGridSize\":{s},",
+                "\"spacing\":0,\"padding\":0,\"tags\":[],\"tagsSourceEnumUid\":null,\"enumTags\":[],\"customData\":[],",
+                "\"savedSelections\":[],\"cachedPixelData\":null}}],",
+                "\"enums\":[],\"externalEnums\":[],\"levelFields\":[]}},"
+            ),
+            int_grid = layer_def("IntGrid", INT_GRID_UID, &values.join(","), "null"),
+            tiles = layer_def("Tiles", TILES_UID, "", &TILESET_UID.to_string()),
+            columns = self.columns,
+            rows = rows,
+            uid = TILESET_UID,
+            atlas = atlas,
+            aw = self.columns * s,
+            ah = rows * s,
+            s = s,
+        );
+
+        // Level and layer instances, from top to bottom.
+        let layer = |kind: &str, uid: usize, csv: &str, tiles: &str| {
+            let tileset = if kind == "Tiles" {
+                (TILESET_UID.to_string(), format!("\"{}\"", atlas))
+            } else {
+                (String::from("null"), String::from("null"))
+            };
+            format!(
+                concat!(
+                    "{{\"__identifier\":\"{name}\",\"__type\":\"{kind}\",\"__cWid\":{w},\"__cHei\":{h},\"__gridSize\":{s},",
+                    "\"__opacity\":1,\"__pxTotalOffsetX\":0,\"__pxTotalOffsetY\":0,\"__tilesetDefUid\":{tileset},",
+                    "\"__tilesetRelPath\":{path},\"iid\":\"{iid}\",\"levelId\":0,\"layerDefUid\":{uid},",
+                    "\"pxOffsetX\":0,\"pxOffsetY\":0,\"visible\":true,\"optionalRules\":[],\"intGridCsv\":[{csv}],",
+                    "\"autoLayerTiles\":[],\"seed\":0,\"overrideTilesetUid\":null,\"gridTiles\":[{tiles}],\"entityInstances\":[]}}"
+                ),
+                name = if kind == "IntGrid" { "Modules" } else { kind },
+                kind = kind,
+                w = width,
+                h = height,
+                s = s,
+                tileset = tileset.0,
+                path = tileset.1,
+                iid = iid(uid + 1),
+                uid = uid,
+                csv = csv,
+                tiles = tiles,
+            )
+        };
+        let csv: Vec<String> = int_grid.iter().map(|value| value.to_string()).collect();
+        let _ = write!(
+            json,
+            concat!(
+                "\"levels\":[{{\"identifier\":\"Level_0\",\"iid\":\"{iid}\",\"uid\":0,\"worldX\":0,\"worldY\":0,",
+                "\"worldDepth\":0,\"pxWid\":{pw},\"pxHei\":{ph},\"__bgColor\":\"#696A79\",\"bgColor\":null,",
+                "\"useAutoIdentifier\":true,\"bgRelPath\":null,\"bgPos\":null,\"bgPivotX\":0.5,\"bgPivotY\":0.5,",
+                "\"__smartColor\":\"#ADADB5\",\"__bgPos\":null,\"externalRelPath\":null,\"fieldInstances\":[],",
+                "\"layerInstances\":[{int_grid},{tiles}],\"__neighbours\":[]}}]}}"
+            ),
+            iid = iid(2),
+            pw = width * s,
+            ph = height * s,
+            int_grid = layer("IntGrid", INT_GRID_UID, &csv.join(","), ""),
+            tiles = layer("Tiles", TILES_UID, "", &tiles.join(",")),
+        );
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// 3x1 wave with modules 0 and 2, and an empty slot in the middle.
+    fn wave() -> Wave {
+        testing::wave(3, 1, &[&[0], &[], &[2]])
+    }
+
+    #[test]
+    fn test_int_grid() {
+        let project = LdtkProject::new("atlas.png", 2, vec!["a".into(); 3], 8);
+        assert_eq!(project.int_grid(&wave()), vec![1, 0, 3]);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let names = ["t 1", "t_1", "1a", "", "é\"x"].map(String::from);
+        assert_eq!(identifiers(&names), ["t_1", "t_1_2", "_1a", "_", "__x"]);
+    }
+
+    #[test]
+    fn test_json() {
+        let names = vec!["a".into(), "b".into(), "c 1".into()];
+        let project = LdtkProject::new("atlas\n.png", 2, names, 8);
+        let json: serde_json::Value = serde_json::from_str(&project.to_json(&wave())).unwrap();
+
+        let tileset = &json["defs"]["tilesets"][0];
+        assert_eq!(tileset["relPath"], "atlas\n.png");
+        assert_eq!(
+            (tileset["pxWid"].as_u64(), tileset["pxHei"].as_u64()),
+            (Some(16), Some(16))
+        );
+        assert_eq!(
+            json["defs"]["layers"][0]["intGridValues"][2]["identifier"],
+            "c_1"
+        );
+
+        let level = &json["levels"][0];
+        assert_eq!(level["pxWid"], 24);
+        let layers = level["layerInstances"].as_array().unwrap();
+        assert_eq!(layers[0]["intGridCsv"], serde_json::json!([1, 0, 3]));
+
+        // Module 2 is the first tile of the second row of the atlas.
+        let tiles = layers[1]["gridTiles"].as_array().unwrap();
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[1]["px"], serde_json::json!([16, 0]));
+        assert_eq!(tiles[1]["src"], serde_json::json!([0, 8]));
+        assert_eq!(tiles[1]["t"], 2);
+    }
+}
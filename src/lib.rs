@@ -3,14 +3,53 @@
 //! The wave function collapse algorithm is a recursive algorithm that picks a random tile
 //! for a slot on the output image and removes impossible neighbors until only a single possibility remains
 
+pub use analysis::*;
 pub use conector::*;
+pub use constraint::*;
 pub use direction::*;
+pub use dot::DotOptions;
+pub use edge::*;
 pub use features::*;
+pub use ldtk::*;
+pub use pixel::*;
+#[cfg(feature = "image")]
+pub use recorder::*;
+#[cfg(feature = "image")]
+pub use render::*;
+pub use socket::*;
+pub use text::*;
+pub use tiled::*;
+#[cfg(feature = "tileset")]
+pub use tileset::*;
+pub use transform::*;
 pub use wave::*;
 
+mod analysis;
 mod conector;
+mod constraint;
 mod direction;
+mod dot;
+mod edge;
+mod escape;
 mod features;
+#[cfg(feature = "gumin")]
+mod gumin;
+mod ldtk;
 mod module;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod pixel;
+#[cfg(feature = "image")]
+mod recorder;
+#[cfg(feature = "image")]
+mod render;
 mod slot;
+mod socket;
+#[cfg(test)]
+mod testing;
+mod text;
+mod tiled;
+#[cfg(feature = "tileset")]
+mod tileset;
+mod transform;
 mod wave;
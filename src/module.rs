@@ -1,13 +1,20 @@
 use crate::{conector::ConnectorID, direction::Direction, slot::Slot};
 
 /// Module represents a tile that can inhabit a slot.
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
     pub index: usize,
     /// Valid connectors for each direction.
     pub connectors: [ConnectorID; 4],
-    // TODO: add Image field. (for now, we just use the index)
-    //pub image: Box<dyn Image>,
+    /// Relative probability of the module being chosen when a slot collapses.
+    pub weight: f32,
+}
+
+impl Default for Module {
+    fn default() -> Self {
+        Self::new(0)
+    }
 }
 
 impl Module {
@@ -15,6 +22,7 @@ impl Module {
         Self {
             index,
             connectors: [ConnectorID::default(); 4],
+            weight: 1.0,
         }
     }
 
@@ -57,7 +65,7 @@ mod tests {
         };
 
         let slot = Slot {
-            superposition: vec![module_a.clone()],
+            superposition: vec![module_a],
             x: 0,
             y: 0,
         };
@@ -0,0 +1,398 @@
+//! Solve a wave on several threads with [rayon](https://docs.rs/rayon).
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+
+use crate::{direction::Direction, slot::Slot, wave::Wave};
+
+/// Number of tries to solve an area of the grid before giving up.
+const AREA_ATTEMPTS: usize = 16;
+
+/// A rectangle of the grid solved on its own.
+#[derive(Debug, Clone, Copy)]
+struct Area {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    /// Sides whose neighbors are kept and constrain the area, in the order of [`Direction::all`].
+    fixed: [bool; 4],
+}
+
+impl Area {
+    /// Returns the indices of the slots of a band in the order they are collapsed, across the band
+    /// from one fixed side to the other, so that each line can still be completed when it is reached.
+    /// Areas without fixed sides use the heuristic of the wave.
+    fn order(&self) -> Option<Vec<usize>> {
+        let [up, right, down, left] = self.fixed;
+        if left || right {
+            Some((0..self.width * self.height).collect())
+        } else if up || down {
+            Some(
+                (0..self.width)
+                    .flat_map(|x| (0..self.height).map(move |y| y * self.width + x))
+                    .collect(),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the seed of an attempt, derived from the base seed.
+fn attempt_seed(seed: u64, attempt: usize) -> u64 {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(attempt as u64);
+    rng.next_u64()
+}
+
+impl<T: Clone + Send + Sync> Wave<T> {
+    /// Run `attempts` independent generations in parallel, each seeded from `seed`,
+    /// and returns the wave of the first one that collapsed.
+    ///
+    /// The first attempt is the one with the lowest number, not the fastest,
+    /// so the result only depends on `seed`. The attempts after it are cancelled.
+    /// The constraints, heuristic and payloads of the wave are kept, its grid is ignored.
+    /// ```
+    /// use billow::Wave;
+    /// # struct Tile;
+    /// # impl billow::Image for Tile {
+    /// #     fn size(&self) -> (usize, usize) { (1, 1) }
+    /// #     fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] { [0; 4] }
+    /// # }
+    ///
+    /// let wave = Wave::new(&[Tile, Tile], 20, 20);
+    /// let solved = wave.collapse_parallel(32, 42).unwrap();
+    /// assert!(solved.is_collapsed());
+    /// ```
+    pub fn collapse_parallel(&self, attempts: usize, seed: u64) -> Result<Wave<T>, String> {
+        let found = AtomicUsize::new(usize::MAX);
+        // Error of the first failed attempt, reported if none succeeds.
+        let error: Mutex<Option<(usize, String)>> = Mutex::new(None);
+
+        let wave = (0..attempts).into_par_iter().find_map_first(|attempt| {
+            let mut wave = self.clone();
+            wave.set_seed(attempt_seed(seed, attempt));
+            wave.initialize();
+            while !wave.is_collapsed() {
+                if found.load(Ordering::Relaxed) < attempt {
+                    return None;
+                }
+                if let Err(err) = wave.collapse(1) {
+                    let mut error = error.lock().expect("Attempt error poisoned");
+                    if error.as_ref().map_or(true, |(first, _)| attempt < *first) {
+                        *error = Some((attempt, err.to_string()));
+                    }
+                    return None;
+                }
+            }
+            found.fetch_min(attempt, Ordering::Relaxed);
+            Some(wave)
+        });
+
+        wave.ok_or_else(|| {
+            let error = error.into_inner().expect("Attempt error poisoned");
+            match error {
+                Some((attempt, err)) => format!(
+                    "All {} attempts failed, attempt {}: {}",
+                    attempts, attempt, err
+                ),
+                None => String::from("No attempts"),
+            }
+        })
+    }
+
+    /// Solve the grid on several threads, in square regions of `size` slots.
+    ///
+    /// The regions are collapsed independently, then a band of slots along each border
+    /// between two regions is solved again, line by line across the border, to match both sides.
+    /// The bands along the horizontal borders span the whole width of the grid.
+    /// The result only depends on `seed`, not on the number of threads.
+    ///
+    /// The current grid is kept, so some slots can be constrained beforehand.
+    /// A band that can not be solved is tried again wider, so regions of a few dozen slots
+    /// leave more room to match their borders than small ones.
+    ///
+    /// Fails if an area can not be solved, or if the wave is periodic
+    /// or has count, connectivity or path constraints, as they span the whole grid.
+    pub fn collapse_regions(&mut self, size: usize, seed: u64) -> Result<(), String> {
+        if size < 2 {
+            return Err(String::from("Regions must be at least 2 slots wide"));
+        }
+        if !self.is_local() {
+            return Err(String::from(
+                "Regions can not solve periodic waves or global constraints",
+            ));
+        }
+        self.check_possible_fn()?;
+        let (width, height) = (self.width(), self.height());
+        if self.grid.len() != width * height {
+            self.reset_grid();
+        }
+        self.history.clear();
+
+        let original = self.grid.clone();
+        // Bands that fail are tried again wider, without touching the bands next to them.
+        let mut margins = vec![(size / 4).max(1), (size - 1) / 2];
+        margins.dedup();
+        let mut stream = 0;
+
+        let regions: Vec<Vec<Area>> = (0..height)
+            .step_by(size)
+            .flat_map(|y| {
+                (0..width).step_by(size).map(move |x| {
+                    vec![Area {
+                        x,
+                        y,
+                        width: size.min(width - x),
+                        height: size.min(height - y),
+                        fixed: [false; 4],
+                    }]
+                })
+            })
+            .collect();
+        self.solve_areas(&regions, &original, seed, &mut stream)?;
+
+        // Vertical borders, the ends of the bands are fixed with the horizontal ones.
+        let columns: Vec<Vec<Area>> = (size..width)
+            .step_by(size)
+            .flat_map(|x| (0..height).step_by(size).map(move |y| (x, y)))
+            .map(|(x, y)| {
+                margins
+                    .iter()
+                    .map(|margin| Area {
+                        x: x - margin,
+                        y,
+                        width: (x + margin).min(width) - (x - margin),
+                        height: size.min(height - y),
+                        fixed: [false, true, false, true],
+                    })
+                    .collect()
+            })
+            .collect();
+        self.solve_areas(&columns, &original, seed, &mut stream)?;
+
+        // Horizontal borders, across the whole grid so that the ends of the bands are on its edges.
+        let rows: Vec<Vec<Area>> = (size..height)
+            .step_by(size)
+            .map(|y| {
+                margins
+                    .iter()
+                    .map(|margin| Area {
+                        x: 0,
+                        y: y - margin,
+                        width,
+                        height: (y + margin).min(height) - (y - margin),
+                        fixed: [true, false, true, false],
+                    })
+                    .collect()
+            })
+            .collect();
+        self.solve_areas(&rows, &original, seed, &mut stream)?;
+        Ok(())
+    }
+
+    /// Solve areas that do not touch each other in parallel.
+    /// Each area is given as a list of candidates, tried in order until one is solved.
+    fn solve_areas(
+        &mut self,
+        areas: &[Vec<Area>],
+        original: &[Slot],
+        seed: u64,
+        stream: &mut usize,
+    ) -> Result<(), String> {
+        let wave = &*self;
+        let solved: Vec<Result<(Area, Vec<Slot>), String>> = areas
+            .par_iter()
+            .enumerate()
+            .map(|(idx, candidates)| {
+                let seed = attempt_seed(seed, *stream + idx);
+                candidates
+                    .iter()
+                    .find_map(|area| Some((*area, wave.solve_area(area, original, seed)?)))
+                    .ok_or_else(|| {
+                        format!(
+                            "Failed to solve the area at ({}, {})",
+                            candidates[0].x, candidates[0].y
+                        )
+                    })
+            })
+            .collect();
+        *stream += areas.len();
+
+        let width = self.width();
+        for solved in solved {
+            let (area, slots) = solved?;
+            for mut slot in slots {
+                slot.x += area.x;
+                slot.y += area.y;
+                let idx = slot.y * width + slot.x;
+                self.grid[idx] = slot;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the collapsed slots of an area reset to the original grid,
+    /// with coordinates relative to the area.
+    fn solve_area(&self, area: &Area, original: &[Slot], seed: u64) -> Option<Vec<Slot>> {
+        let (width, height) = (self.width(), self.height());
+        let mut wave = self.area(area.x, area.y, area.width, area.height);
+        wave.set_seed(seed);
+
+        for slot in wave.grid.iter_mut() {
+            let (x, y) = (area.x + slot.x, area.y + slot.y);
+            slot.superposition = original[y * width + x].superposition.clone();
+
+            // Keep the modules that fit next to the fixed neighbors.
+            for direction in Direction::all() {
+                if !area.fixed[direction as usize] {
+                    continue;
+                }
+                let neighbor = match direction {
+                    Direction::Up if slot.y == 0 && y > 0 => (x, y - 1),
+                    Direction::Right if slot.x == area.width - 1 && x + 1 < width => (x + 1, y),
+                    Direction::Down if slot.y == area.height - 1 && y + 1 < height => (x, y + 1),
+                    Direction::Left if slot.x == 0 && x > 0 => (x - 1, y),
+                    _ => continue,
+                };
+                let from = &self.grid[neighbor.1 * width + neighbor.0];
+                let to = Slot {
+                    x,
+                    y,
+                    superposition: slot.superposition.clone(),
+                };
+                slot.retain(|module| {
+                    (self.is_possible_fn)(*module, from.clone(), to.clone(), direction.reverse())
+                });
+                if slot.superposition.is_empty() {
+                    return None;
+                }
+            }
+        }
+        wave.propagate((0..wave.grid.len()).collect()).ok()?;
+
+        let start = wave.grid.clone();
+        let order = area.order();
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        for _ in 0..AREA_ATTEMPTS {
+            wave.grid.clone_from(&start);
+            let solved = match &order {
+                Some(order) => order.iter().all(|idx| {
+                    wave.grid[*idx].superposition.len() == 1 || {
+                        wave.grid[*idx].collapse(&mut rng);
+                        wave.propagate(vec![*idx]).is_ok()
+                    }
+                }),
+                None => loop {
+                    if wave.is_collapsed() {
+                        break true;
+                    }
+                    // Only slots without any possible module are left.
+                    let Some(slot) = wave.observe() else {
+                        break false;
+                    };
+                    if wave.propagate(vec![slot.y * area.width + slot.x]).is_err() {
+                        break false;
+                    }
+                },
+            };
+            if solved {
+                return Some(wave.grid);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{is_valid, Edges};
+
+    fn wave() -> Wave {
+        let tiles = [
+            Edges([0, 0, 0, 0]),
+            Edges([0, 1, 0, 1]),
+            Edges([1, 0, 1, 0]),
+            Edges([0, 1, 1, 0]),
+            Edges([1, 1, 1, 1]),
+        ];
+        Wave::with_custom_constraint(&tiles, 12, 12, crate::get_constraint_fn(1))
+    }
+
+    /// Lines that can end anywhere, so that any border can be matched.
+    fn roads() -> Wave {
+        let tiles = [
+            Edges([0, 0, 0, 0]),
+            Edges([0, 1, 0, 1]),
+            Edges([1, 0, 1, 0]),
+            Edges([1, 1, 1, 1]),
+            Edges([1, 0, 0, 0]),
+            Edges([0, 1, 0, 0]),
+            Edges([0, 0, 1, 0]),
+            Edges([0, 0, 0, 1]),
+        ];
+        Wave::with_custom_constraint(&tiles, 12, 12, crate::get_constraint_fn(1))
+    }
+
+    fn indices(wave: &Wave) -> Vec<usize> {
+        wave.grid
+            .iter()
+            .map(|slot| slot.superposition[0].index)
+            .collect()
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let a = wave().collapse_parallel(16, 42).unwrap();
+        let b = wave().collapse_parallel(16, 42).unwrap();
+        assert!(a.is_collapsed());
+        assert!(is_valid(&a));
+        assert_eq!(indices(&a), indices(&b));
+
+        // Same result as the first successful attempt run alone.
+        let single = (0..16)
+            .find_map(|attempt| {
+                let mut single = wave();
+                single.set_seed(attempt_seed(42, attempt));
+                single.initialize();
+                (single.collapse(1000).is_ok() && single.is_collapsed()).then_some(single)
+            })
+            .expect("No attempt succeeded");
+        assert_eq!(indices(&a), indices(&single));
+    }
+
+    #[test]
+    fn test_regions() {
+        let mut a = roads();
+        a.collapse_regions(6, 3).unwrap();
+        assert!(a.is_collapsed());
+        assert!(is_valid(&a));
+
+        let mut b = roads();
+        b.collapse_regions(6, 3).unwrap();
+        assert_eq!(indices(&a), indices(&b));
+
+        assert!(roads().collapse_regions(1, 3).is_err());
+        let mut periodic = roads();
+        periodic.set_periodic(true);
+        assert!(periodic.collapse_regions(6, 3).is_err());
+    }
+
+    #[test]
+    fn test_failure() {
+        // Nothing fits next to these tiles.
+        let tiles = [Edges([0, 1, 2, 3]), Edges([4, 5, 6, 7])];
+        let wave = Wave::with_custom_constraint(&tiles, 2, 2, crate::get_constraint_fn(1));
+        let err = wave.collapse_parallel(4, 0).err().unwrap();
+        assert!(err.starts_with("All 4 attempts failed, attempt 0: "));
+        assert_eq!(wave.collapse_parallel(0, 0).err().unwrap(), "No attempts");
+    }
+}
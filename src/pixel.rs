@@ -0,0 +1,280 @@
+//! Formats of the pixels of the input images.
+
+use crate::wave::Image;
+
+/// Channels of a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    /// Luminance.
+    Gray,
+    /// Luminance and alpha.
+    GrayAlpha,
+    /// Red, green and blue.
+    Rgb,
+    /// Red, green, blue and alpha.
+    Rgba,
+    /// Index of a color in a palette.
+    Indexed,
+}
+
+impl Channels {
+    /// Returns the number of channels of a pixel.
+    pub fn count(&self) -> usize {
+        match self {
+            Channels::Gray | Channels::Indexed => 1,
+            Channels::GrayAlpha => 2,
+            Channels::Rgb => 3,
+            Channels::Rgba => 4,
+        }
+    }
+}
+
+/// Type of the value of a channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// 8-bit integer, from 0 to 255.
+    U8,
+    /// 16-bit integer, from 0 to 65535.
+    U16,
+    /// 16-bit float, from 0.0 to 1.0.
+    F16,
+    /// 32-bit float, from 0.0 to 1.0.
+    F32,
+}
+
+impl Depth {
+    /// Returns the size of a channel in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            Depth::U8 => 1,
+            Depth::U16 | Depth::F16 => 2,
+            Depth::F32 => 4,
+        }
+    }
+}
+
+/// How the pixels of an image are stored, returned by [`Image::format`].
+///
+/// [`Image::get_pixel_at`] always returns 8-bit RGBA, the format tells what it was converted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    pub channels: Channels,
+    pub depth: Depth,
+}
+
+impl PixelFormat {
+    /// 8-bit RGBA, the format of [`Image::get_pixel_at`].
+    pub const RGBA8: PixelFormat = PixelFormat::new(Channels::Rgba, Depth::U8);
+
+    pub const fn new(channels: Channels, depth: Depth) -> Self {
+        Self { channels, depth }
+    }
+
+    /// Returns the size of a pixel in bytes.
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.channels.count() * self.depth.bytes()
+    }
+
+    /// Returns the 8-bit RGBA color of a pixel from the 8-bit values of its channels,
+    /// to help implement [`Image::get_pixel_at`] for other formats.
+    /// Gray is copied to red, green and blue, and a missing alpha is opaque.
+    ///
+    /// Panics if there are fewer values than channels.
+    pub fn to_rgba(&self, values: &[u8]) -> [u8; 4] {
+        match self.channels {
+            Channels::Gray | Channels::Indexed => [values[0], values[0], values[0], 255],
+            Channels::GrayAlpha => [values[0], values[0], values[0], values[1]],
+            Channels::Rgb => [values[0], values[1], values[2], 255],
+            Channels::Rgba => [values[0], values[1], values[2], values[3]],
+        }
+    }
+}
+
+/// Value of a channel that can be converted to 8 bits.
+#[cfg_attr(not(any(feature = "image", feature = "bevy")), allow(dead_code))]
+pub(crate) trait Channel: Copy {
+    fn to_u8(self) -> u8;
+}
+
+impl Channel for u8 {
+    fn to_u8(self) -> u8 {
+        self
+    }
+}
+
+impl Channel for u16 {
+    /// Rounds to the nearest 8-bit value, 65535 is 255.
+    fn to_u8(self) -> u8 {
+        ((self as u32 + 128) / 257) as u8
+    }
+}
+
+impl Channel for f32 {
+    /// Clamps to `0.0..=1.0` and rounds to the nearest 8-bit value.
+    fn to_u8(self) -> u8 {
+        (self.clamp(0.0, 1.0) * 255.0).round() as u8
+    }
+}
+
+/// Converts a half precision float to a single precision one.
+#[cfg_attr(not(feature = "bevy"), allow(dead_code))]
+pub(crate) fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+/// Returns the 8-bit value of a little-endian channel of the given depth.
+/// Normalized integers are scaled from their whole range, others are saturated at 255.
+#[cfg_attr(not(feature = "bevy"), allow(dead_code))]
+pub(crate) fn decode_channel(bytes: &[u8], depth: Depth, normalized: bool) -> u8 {
+    match depth {
+        Depth::U8 => bytes[0],
+        Depth::U16 => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            if normalized {
+                value.to_u8()
+            } else {
+                value.min(255) as u8
+            }
+        }
+        Depth::F16 => f16_to_f32(u16::from_le_bytes([bytes[0], bytes[1]])).to_u8(),
+        Depth::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).to_u8(),
+    }
+}
+
+/// An image whose pixels are indices in a palette of colors, e.g. a palettized PNG.
+/// ```
+/// use billow::{Image, Indexed};
+///
+/// let palette = vec![[0, 0, 0, 255], [255, 255, 255, 255]];
+/// let image = Indexed::new(2, 1, vec![0, 1], palette).unwrap();
+/// assert_eq!(image.get_pixel_at(1, 0), [255, 255, 255, 255]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Indexed {
+    width: usize,
+    height: usize,
+    indices: Vec<u8>,
+    palette: Vec<[u8; 4]>,
+}
+
+impl Indexed {
+    /// Create an image from the index of each pixel, row by row, and the RGBA colors of the palette.
+    /// Fails if there is not one index per pixel or if an index is outside the palette.
+    pub fn new(
+        width: usize,
+        height: usize,
+        indices: Vec<u8>,
+        palette: Vec<[u8; 4]>,
+    ) -> Result<Self, String> {
+        if indices.len() != width * height {
+            return Err(format!(
+                "Expected {} indices, got {}",
+                width * height,
+                indices.len()
+            ));
+        }
+        if let Some(index) = indices.iter().find(|idx| **idx as usize >= palette.len()) {
+            return Err(format!(
+                "Index {} is outside the palette of {} colors",
+                index,
+                palette.len()
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            indices,
+            palette,
+        })
+    }
+
+    /// Returns the index of the pixel at `x`, `y`.
+    pub fn index_at(&self, x: usize, y: usize) -> u8 {
+        self.indices[x + y * self.width]
+    }
+}
+
+impl Image for Indexed {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+    fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+        self.palette[self.index_at(x, y) as usize]
+    }
+    fn format(&self) -> Result<PixelFormat, String> {
+        Ok(PixelFormat::new(Channels::Indexed, Depth::U8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel() {
+        assert_eq!(
+            [0u16, 128, 32767, 65535].map(Channel::to_u8),
+            [0, 0, 127, 255]
+        );
+        assert_eq!(
+            [-1.0f32, 0.0, 0.5, 1.0, 2.0].map(Channel::to_u8),
+            [0, 0, 128, 255, 255]
+        );
+        assert_eq!(0x12u16.to_u8(), 0);
+        assert_eq!(0x1234u16.to_u8(), 0x12);
+    }
+
+    #[test]
+    fn test_decode_channel() {
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0x3800), 0.5);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+
+        let bytes = 0x8080u16.to_le_bytes();
+        assert_eq!(decode_channel(&bytes, Depth::U16, true), 128);
+        assert_eq!(decode_channel(&bytes, Depth::U16, false), 255);
+        assert_eq!(decode_channel(&7u16.to_le_bytes(), Depth::U16, false), 7);
+        assert_eq!(
+            decode_channel(&0x3800u16.to_le_bytes(), Depth::F16, true),
+            128
+        );
+        assert_eq!(decode_channel(&0.5f32.to_le_bytes(), Depth::F32, true), 128);
+        assert_eq!(decode_channel(&[9], Depth::U8, false), 9);
+    }
+
+    #[test]
+    fn test_to_rgba() {
+        let gray = PixelFormat::new(Channels::GrayAlpha, Depth::U16);
+        assert_eq!(gray.bytes_per_pixel(), 4);
+        assert_eq!(gray.to_rgba(&[10, 20]), [10, 10, 10, 20]);
+        let rgb = PixelFormat::new(Channels::Rgb, Depth::F32);
+        assert_eq!(rgb.bytes_per_pixel(), 12);
+        assert_eq!(rgb.to_rgba(&[1, 2, 3]), [1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn test_indexed() {
+        let palette = vec![[1, 2, 3, 4], [5, 6, 7, 8]];
+        let image = Indexed::new(2, 2, vec![0, 1, 1, 0], palette.clone()).unwrap();
+        assert_eq!(image.size(), (2, 2));
+        assert_eq!(image.index_at(0, 1), 1);
+        assert_eq!(image.get_pixel_at(0, 1), [5, 6, 7, 8]);
+        assert_eq!(image.format().unwrap().channels, Channels::Indexed);
+
+        let err = Indexed::new(2, 2, vec![0, 1], palette.clone()).unwrap_err();
+        assert_eq!(err, "Expected 4 indices, got 2");
+        let err = Indexed::new(1, 1, vec![2], palette).unwrap_err();
+        assert_eq!(err, "Index 2 is outside the palette of 2 colors");
+    }
+}
@@ -0,0 +1,191 @@
+//! Record the progress of a wave as an animated GIF or APNG.
+
+use std::{fs::File, io::BufWriter, io::Write, path::Path};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    error::{EncodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+    Delay, Frame, ImageError, ImageFormat, ImageResult, RgbaImage,
+};
+
+use crate::{
+    render::{render, RenderOptions},
+    wave::{Image, Wave},
+};
+
+/// Collects frames of a wave while it collapses.
+/// ```no_run
+/// use billow::{Recorder, Wave};
+/// use image::DynamicImage;
+///
+/// let tiles: Vec<DynamicImage> = vec![/* ... */];
+/// let mut wave = Wave::new(&tiles, 20, 20);
+/// let mut recorder = Recorder::new(&tiles).every(5);
+///
+/// wave.initialize();
+/// wave.collapse_with(1000, |wave| recorder.observe(wave)).unwrap();
+/// recorder.capture(&wave);
+/// recorder.save("solve.gif").unwrap();
+/// ```
+pub struct Recorder<'a, I: Image> {
+    tiles: &'a [I],
+    options: RenderOptions,
+    every: usize,
+    delay: u32,
+    observations: usize,
+    frames: Vec<RgbaImage>,
+}
+
+impl<'a, I: Image> Recorder<'a, I> {
+    /// Create a recorder that draws the wave with the images of its modules.
+    pub fn new(tiles: &'a [I]) -> Self {
+        Self {
+            tiles,
+            options: RenderOptions::default(),
+            every: 1,
+            delay: 100,
+            observations: 0,
+            frames: vec![],
+        }
+    }
+
+    /// Keep one frame every `n` observations.
+    pub fn every(mut self, n: usize) -> Self {
+        self.every = n.max(1);
+        self
+    }
+
+    /// Set how the frames are drawn, e.g. whether superposed slots are blended.
+    pub fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the duration of each frame in milliseconds.
+    pub fn delay(mut self, milliseconds: u32) -> Self {
+        self.delay = milliseconds;
+        self
+    }
+
+    /// Count an observation of the wave and keep a frame if it is due.
+    /// Meant to be used as the observer of [`Wave::collapse_with`].
+    pub fn observe<T>(&mut self, wave: &Wave<T>) {
+        if self.observations % self.every == 0 {
+            self.capture(wave);
+        }
+        self.observations += 1;
+    }
+
+    /// Keep a frame of the wave, regardless of the number of observations.
+    pub fn capture<T>(&mut self, wave: &Wave<T>) {
+        self.frames.push(render(wave, self.tiles, &self.options));
+    }
+
+    /// Drop the recorded frames, e.g. to start over after a contradiction.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.observations = 0;
+    }
+
+    /// Returns the recorded frames.
+    pub fn frames(&self) -> &[RgbaImage] {
+        &self.frames
+    }
+
+    /// Write the frames as an animated GIF that loops forever.
+    pub fn write_gif(&self, writer: impl Write) -> ImageResult<()> {
+        let mut encoder = GifEncoder::new(writer);
+        encoder.set_repeat(Repeat::Infinite)?;
+        for frame in &self.frames {
+            let delay = Delay::from_numer_denom_ms(self.delay, 1);
+            encoder.encode_frame(Frame::from_parts(frame.clone(), 0, 0, delay))?;
+        }
+        Ok(())
+    }
+
+    /// Write the frames as an animated PNG that loops forever.
+    pub fn write_apng(&self, writer: impl Write) -> ImageResult<()> {
+        let error = |err: png::EncodingError| {
+            ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Exact(ImageFormat::Png),
+                err,
+            ))
+        };
+        let (width, height) = self.frames.first().map_or((1, 1), |f| f.dimensions());
+
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.frames.len().max(1) as u32, 0)
+            .map_err(error)?;
+        let delay = self.delay.min(u16::MAX as u32) as u16;
+        encoder.set_frame_delay(delay, 1000).map_err(error)?;
+
+        let mut writer = encoder.write_header().map_err(error)?;
+        if self.frames.is_empty() {
+            writer.write_image_data(&[0; 4]).map_err(error)?;
+        }
+        for frame in &self.frames {
+            writer.write_image_data(frame.as_raw()).map_err(error)?;
+        }
+        writer.finish().map_err(error)
+    }
+
+    /// Save the frames to a `.gif` or `.png` file, depending on the extension.
+    pub fn save(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path)?;
+        let file = BufWriter::new(File::create(path).map_err(ImageError::IoError)?);
+        match format {
+            ImageFormat::Gif => self.write_gif(file),
+            ImageFormat::Png => self.write_apng(file),
+            _ => Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormatHint::Exact(format),
+                    UnsupportedErrorKind::Format(ImageFormatHint::Exact(format)),
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Tile;
+
+    fn record(every: usize) -> Recorder<'static, Tile> {
+        let mut wave = Wave::new(&[Tile(0), Tile(1)], 3, 3);
+        let mut recorder = Recorder::new(&[Tile(0), Tile(1)]).every(every);
+        wave.initialize();
+        wave.collapse_with(100, |wave| recorder.observe(wave))
+            .unwrap();
+        recorder
+    }
+
+    #[test]
+    fn test_every() {
+        // The first iteration propagates the slot collapsed by `initialize`,
+        // then each one collapses one of the 8 other slots.
+        assert_eq!(record(1).frames().len(), 9);
+        assert_eq!(record(3).frames().len(), 3);
+        assert_eq!(record(3).frames()[0].dimensions(), (9, 9));
+    }
+
+    #[test]
+    fn test_write() {
+        let recorder = record(2);
+
+        let mut gif = vec![];
+        recorder.write_gif(&mut gif).unwrap();
+        assert!(gif.starts_with(b"GIF89a"));
+
+        let mut apng = vec![];
+        recorder.write_apng(&mut apng).unwrap();
+        let decoder = png::Decoder::new(apng.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control().unwrap();
+        assert_eq!(control.num_frames, 5);
+    }
+}
@@ -0,0 +1,130 @@
+//! Compose an image of a wave from the images of its modules.
+
+use image::{Rgba, RgbaImage};
+
+use crate::wave::{Image, Wave};
+
+/// How a slot with more than one possible module is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uncollapsed {
+    /// Average of the images of the possible modules.
+    Blend,
+    /// A plain color.
+    Color([u8; 4]),
+}
+
+/// Options of [`render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// How slots that are not collapsed are drawn.
+    pub uncollapsed: Uncollapsed,
+    /// Color of the slots without any possible module.
+    pub contradiction: [u8; 4],
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            uncollapsed: Uncollapsed::Blend,
+            contradiction: [75, 15, 15, 255],
+        }
+    }
+}
+
+/// Draw every slot of the wave with the image of its module.
+///
+/// `tiles` are the images of the modules, usually the input of the wave, and must all have the same size.
+/// ```
+/// use billow::{render, RenderOptions, Wave};
+/// use image::DynamicImage;
+///
+/// let tiles = vec![DynamicImage::new_rgba8(3, 3)];
+/// let mut wave = Wave::new(&tiles, 4, 2);
+/// wave.initialize();
+/// let image = render(&wave, &tiles, &RenderOptions::default());
+/// assert_eq!(image.dimensions(), (12, 6));
+/// ```
+pub fn render<T>(wave: &Wave<T>, tiles: &[impl Image], options: &RenderOptions) -> RgbaImage {
+    let (tw, th) = tiles.first().map_or((0, 0), |tile| tile.size());
+    let mut image = RgbaImage::new((wave.width() * tw) as u32, (wave.height() * th) as u32);
+
+    for slot in wave.grid.iter() {
+        let modules: Vec<_> = slot
+            .superposition
+            .iter()
+            .filter_map(|module| tiles.get(module.index))
+            .collect();
+
+        for y in 0..th {
+            for x in 0..tw {
+                let pixel = match (modules.as_slice(), options.uncollapsed) {
+                    ([], _) => options.contradiction,
+                    ([tile], _) => tile.get_pixel_at(x, y),
+                    (_, Uncollapsed::Color(color)) => color,
+                    (_, Uncollapsed::Blend) => {
+                        let mut sum = [0usize; 4];
+                        for tile in modules.iter() {
+                            let pixel = tile.get_pixel_at(x, y);
+                            for (total, value) in sum.iter_mut().zip(pixel) {
+                                *total += value as usize;
+                            }
+                        }
+                        sum.map(|total| (total / modules.len()) as u8)
+                    }
+                };
+                image.put_pixel(
+                    (slot.x * tw + x) as u32,
+                    (slot.y * th + y) as u32,
+                    Rgba(pixel),
+                );
+            }
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x2 image of a single color.
+    struct Color([u8; 4]);
+
+    impl Image for Color {
+        fn size(&self) -> (usize, usize) {
+            (2, 2)
+        }
+        fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let tiles = [Color([0, 0, 0, 255]), Color([200, 100, 50, 255])];
+        let mut wave = Wave::new(&tiles, 3, 1);
+        wave.initialize();
+        let mut module = wave.grid[0].superposition[0];
+        module.index = 1;
+        wave.grid[0].superposition = vec![module];
+        wave.grid[1].superposition.clear();
+        module.index = 0;
+        let mut other = module;
+        other.index = 1;
+        wave.grid[2].superposition = vec![module, other];
+
+        let image = render(&wave, &tiles, &RenderOptions::default());
+        assert_eq!(image.dimensions(), (6, 2));
+        assert_eq!(image[(1, 1)], Rgba([200, 100, 50, 255]));
+        assert_eq!(image[(2, 0)], Rgba([75, 15, 15, 255]));
+        assert_eq!(image[(5, 1)], Rgba([100, 50, 25, 255]));
+
+        let options = RenderOptions {
+            uncollapsed: Uncollapsed::Color([0, 0, 0, 0]),
+            contradiction: [255, 0, 0, 255],
+        };
+        let image = render(&wave, &tiles, &options);
+        assert_eq!(image[(2, 0)], Rgba([255, 0, 0, 255]));
+        assert_eq!(image[(5, 1)], Rgba([0, 0, 0, 0]));
+    }
+}
@@ -1,9 +1,10 @@
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, Rng};
 
 use crate::module::Module;
 
 /// A slot is a place in agrid where modules can be placed.
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Slot {
     /// X coordinate of the slot.
     pub x: usize,
@@ -14,11 +15,25 @@ pub struct Slot {
 }
 
 impl Slot {
-    pub fn collapse(&mut self) {
-        if let Some(module) = self.superposition.choose(&mut rand::thread_rng()) {
+    /// Keep a single random module, chosen according to the weight of the modules.
+    pub fn collapse(&mut self, rng: &mut impl Rng) {
+        let chosen = self
+            .superposition
+            .choose_weighted(rng, |module| module.weight)
+            .ok()
+            .or_else(|| self.superposition.first());
+        if let Some(module) = chosen {
             self.superposition = vec![*module];
         }
     }
+
+    /// Keep only the modules that match the predicate.
+    /// Returns true if any module was removed.
+    pub fn retain(&mut self, f: impl FnMut(&Module) -> bool) -> bool {
+        let len = self.superposition.len();
+        self.superposition.retain(f);
+        self.superposition.len() != len
+    }
 }
 
 #[cfg(test)]
@@ -54,7 +69,30 @@ mod tests {
             ],
         };
 
-        slot.collapse();
+        slot.collapse(&mut rand::thread_rng());
         assert_eq!(slot.superposition.len(), 1);
     }
+
+    #[test]
+    fn test_collapse_weighted() {
+        let heavy = Module {
+            index: 1,
+            weight: 1.0,
+            ..Default::default()
+        };
+        let never = Module {
+            index: 2,
+            weight: 0.0,
+            ..Default::default()
+        };
+
+        for _ in 0..10 {
+            let mut slot = Slot {
+                superposition: vec![never, heavy, never],
+                ..Default::default()
+            };
+            slot.collapse(&mut rand::thread_rng());
+            assert_eq!(slot.superposition[0].index, 1);
+        }
+    }
 }
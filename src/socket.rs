@@ -0,0 +1,236 @@
+//! Sides made of several connectors, called sockets, e.g. the left, center and right lanes of a road.
+//!
+//! The sockets of a side are listed clockwise around the module: left to right on the up side,
+//! top to bottom on the right side, right to left on the down side and bottom to top on the left side.
+//! Two neighbors fit when their sockets fit one by one, the list of one side against the reversed list of the other.
+
+use std::sync::Arc;
+
+use crate::{
+    conector::ConnectorID,
+    direction::Direction,
+    pixel::PixelFormat,
+    wave::{ConstraintFn, Image, PossibleFn},
+};
+
+/// Returns the sockets of a side of an image, listed clockwise.
+pub type SocketFn = dyn Fn(&dyn Image, Direction) -> Vec<ConnectorID>;
+
+/// Sockets of every module.
+/// ```
+/// use billow::{ConnectorID, Direction, Sockets};
+///
+/// let [road, grass, sand] = ["road", "grass", "sand"].map(ConnectorID::from);
+/// // A road on the left lane, with sand on the right of the up side.
+/// let sides = vec![[vec![road, grass, sand], vec![grass], vec![grass, grass, road], vec![grass]]];
+///
+/// // The up side is compared in reverse with the down side of the module above.
+/// assert!(!Sockets::new(sides.clone()).fits(0, 0, Direction::Up));
+/// assert!(Sockets::new(sides).pair(grass, sand).fits(0, 0, Direction::Up));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sockets {
+    /// Sockets of the sides of each module, in the order of [`Direction::all`].
+    pub sides: Vec<[Vec<ConnectorID>; 4]>,
+    /// Pairs of different sockets that fit together. Equal sockets always fit.
+    pub pairs: Vec<(ConnectorID, ConnectorID)>,
+}
+
+impl Sockets {
+    /// Create the sockets of the modules, indexed like the modules.
+    pub fn new(sides: Vec<[Vec<ConnectorID>; 4]>) -> Self {
+        Self {
+            sides,
+            pairs: vec![],
+        }
+    }
+
+    /// Let two different sockets fit together, in both orders.
+    pub fn pair(mut self, a: ConnectorID, b: ConnectorID) -> Self {
+        self.pairs.push((a, b));
+        self
+    }
+
+    /// Returns true if two sockets fit together.
+    pub fn is_compatible(&self, a: ConnectorID, b: ConnectorID) -> bool {
+        a == b || self.pairs.contains(&(a, b)) || self.pairs.contains(&(b, a))
+    }
+
+    /// Returns true if module `b` can be placed in `direction` of module `a`.
+    pub fn fits(&self, a: usize, b: usize, direction: Direction) -> bool {
+        let (Some(a), Some(b)) = (self.sides.get(a), self.sides.get(b)) else {
+            return false;
+        };
+        let (a, b) = (&a[direction as usize], &b[direction.reverse() as usize]);
+        a.len() == b.len()
+            && a.iter()
+                .zip(b.iter().rev())
+                .all(|(a, b)| self.is_compatible(*a, *b))
+    }
+
+    /// Returns a function for [`Wave::is_possible_fn`](crate::Wave::is_possible_fn) that compares the sockets.
+    pub fn is_possible_fn(self) -> Arc<PossibleFn> {
+        Arc::new(move |module, from, _to, direction| {
+            from.superposition
+                .iter()
+                .any(|m| self.fits(m.index, module.index, direction))
+        })
+    }
+}
+
+/// Returns the connector of a side made of sockets.
+/// The connectors of two sides are equal when their sockets are equal one by one,
+/// so the features that compare connectors, like the connectivity constraint, keep working.
+/// A side with a single socket has the connector of that socket.
+pub fn side_connector(sockets: &[ConnectorID], direction: Direction) -> ConnectorID {
+    match direction {
+        Direction::Up | Direction::Right => ConnectorID::combine(sockets.iter()),
+        Direction::Down | Direction::Left => ConnectorID::combine(sockets.iter().rev()),
+    }
+}
+
+/// Returns a socket function that splits each side of the image in `count` equal parts,
+/// and gives each part the connector found by `constraint_fn`.
+pub fn socket_fn(constraint_fn: Box<ConstraintFn>, count: usize) -> Box<SocketFn> {
+    let count = count.max(1);
+    Box::new(move |image: &dyn Image, direction: Direction| {
+        let (w, h) = image.size();
+        let mut sockets: Vec<ConnectorID> = (0..count)
+            .map(|idx| {
+                let part = |length: usize| (idx * length / count, (idx + 1) * length / count);
+                let segment = match direction {
+                    Direction::Up | Direction::Down => {
+                        let (start, end) = part(w);
+                        Segment::new(image, (start, 0), (end - start, h))
+                    }
+                    Direction::Right | Direction::Left => {
+                        let (start, end) = part(h);
+                        Segment::new(image, (0, start), (w, end - start))
+                    }
+                };
+                constraint_fn(&segment, direction)
+            })
+            .collect();
+        if matches!(direction, Direction::Down | Direction::Left) {
+            sockets.reverse();
+        }
+        sockets
+    })
+}
+
+/// A rectangle of an image.
+struct Segment<'a> {
+    image: &'a dyn Image,
+    offset: (usize, usize),
+    size: (usize, usize),
+}
+
+impl<'a> Segment<'a> {
+    fn new(image: &'a dyn Image, offset: (usize, usize), size: (usize, usize)) -> Self {
+        Self {
+            image,
+            offset,
+            size,
+        }
+    }
+}
+
+impl Image for Segment<'_> {
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+        self.image
+            .get_pixel_at(self.offset.0 + x, self.offset.1 + y)
+    }
+
+    fn format(&self) -> Result<PixelFormat, String> {
+        self.image.format()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, wave::get_constraint_fn, EdgeConstraint, EdgeSampling, Wave};
+
+    /// Tile with a vertical stripe of color in its left third, the rest is black.
+    struct Stripe([u8; 4]);
+
+    impl Image for Stripe {
+        fn size(&self) -> (usize, usize) {
+            (9, 9)
+        }
+        fn get_pixel_at(&self, x: usize, _y: usize) -> [u8; 4] {
+            if x < 3 {
+                self.0
+            } else {
+                [0, 0, 0, 255]
+            }
+        }
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    #[test]
+    fn test_socket_fn() {
+        // A single pixel is sampled on each part.
+        let (red, black) = (ConnectorID::from(RED), ConnectorID::from(BLACK));
+
+        let socket_fn = socket_fn(get_constraint_fn(1), 3);
+        let image = Stripe(RED);
+        assert_eq!(socket_fn(&image, Direction::Up), vec![red, black, black]);
+        assert_eq!(socket_fn(&image, Direction::Right), vec![black; 3]);
+        assert_eq!(socket_fn(&image, Direction::Down), vec![black, black, red]);
+        assert_eq!(socket_fn(&image, Direction::Left), vec![red; 3]);
+    }
+
+    #[test]
+    fn test_fits() {
+        let [a, b, c] = ["a", "b", "c"].map(ConnectorID::from);
+        let sockets = Sockets::new(vec![
+            [vec![a, b, c], vec![a], vec![c, b, a], vec![a]],
+            [vec![a, b, a], vec![a], vec![a, b, a], vec![a, b]],
+        ]);
+
+        assert!(sockets.fits(0, 0, Direction::Up));
+        assert!(!sockets.fits(0, 1, Direction::Down));
+        // Different number of sockets.
+        assert!(!sockets.fits(0, 1, Direction::Right));
+        assert!(!sockets.fits(0, 2, Direction::Up));
+
+        let sockets = sockets.pair(c, a);
+        assert!(sockets.fits(0, 1, Direction::Down));
+        assert!(sockets.fits(1, 0, Direction::Down));
+
+        let connector = |module: usize, direction: Direction| {
+            side_connector(&sockets.sides[module][direction as usize], direction)
+        };
+        assert_eq!(connector(0, Direction::Up), connector(0, Direction::Down));
+        assert_eq!(connector(0, Direction::Right), a);
+        assert_ne!(connector(1, Direction::Down), connector(0, Direction::Up));
+    }
+
+    #[test]
+    fn test_wave() {
+        let images = [Stripe(RED), Stripe(BLACK)];
+        let edges = EdgeConstraint::new().sampling(EdgeSampling::Points(1));
+        let mut wave = Wave::with_sockets(&images, 4, 4, socket_fn(edges.build(), 3));
+        wave.set_seed(3);
+        testing::solve(&mut wave, 100);
+
+        // Stripes continue up and down, and only black is on the right of a stripe.
+        for slot in wave.grid.iter().filter(|slot| slot.x < 3) {
+            let right = &wave.grid[slot.y * 4 + slot.x + 1];
+            if slot.superposition[0].index == 0 {
+                assert_eq!(right.superposition[0].index, 1);
+            }
+        }
+        for slot in wave.grid.iter().filter(|slot| slot.y < 3) {
+            let below = &wave.grid[(slot.y + 1) * 4 + slot.x];
+            assert_eq!(slot.superposition[0].index, below.superposition[0].index);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Helpers shared by the tests.
+
+use crate::{module::Module, Direction, Image, Wave};
+
+/// 8x8 tile with a color for each side, in the order of [`Direction::all`].
+pub(crate) struct Edges(pub [u8; 4]);
+
+impl Image for Edges {
+    fn size(&self) -> (usize, usize) {
+        (8, 8)
+    }
+    fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+        let side = match (x, y) {
+            (_, 0) => 0,
+            (7, _) => 1,
+            (_, 7) => 2,
+            _ => 3,
+        };
+        [self.0[side], 0, 0, 255]
+    }
+}
+
+/// 3x3 black tile with a center pixel of the given red, so every tile fits next to each other.
+pub(crate) struct Tile(pub u8);
+
+impl Image for Tile {
+    fn size(&self) -> (usize, usize) {
+        (3, 3)
+    }
+    fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+        if x == 1 && y == 1 {
+            [self.0, 0, 0, 255]
+        } else {
+            [0, 0, 0, 255]
+        }
+    }
+}
+
+/// Returns a wave of [`Tile`]s whose slots hold the given modules, row by row,
+/// without running the algorithm. An empty slot is a contradiction.
+pub(crate) fn wave(width: usize, height: usize, slots: &[&[usize]]) -> Wave {
+    let count = slots
+        .iter()
+        .copied()
+        .flatten()
+        .max()
+        .map_or(1, |max| max + 1);
+    let tiles: Vec<Tile> = (0..count).map(|_| Tile(0)).collect();
+    let mut wave = Wave::new(&tiles, width, height);
+    wave.initialize();
+    let module = wave.grid[0].superposition[0];
+    for (slot, modules) in wave.grid.iter_mut().zip(slots) {
+        slot.superposition = modules
+            .iter()
+            .map(|&index| Module { index, ..module })
+            .collect();
+    }
+    wave
+}
+
+/// Returns true if every slot is collapsed and fits its right and down neighbors.
+pub(crate) fn is_valid<T>(wave: &Wave<T>) -> bool {
+    let width = wave.width();
+    wave.grid.iter().all(|slot| {
+        let fits = |neighbor: Option<&crate::slot::Slot>, direction| {
+            neighbor.map_or(true, |neighbor| {
+                neighbor.superposition.len() == 1
+                    && (wave.is_possible_fn)(
+                        neighbor.superposition[0],
+                        slot.clone(),
+                        neighbor.clone(),
+                        direction,
+                    )
+            })
+        };
+        let right = wave
+            .grid
+            .get(slot.y * width + slot.x + 1)
+            .filter(|_| slot.x + 1 < width);
+        let down = wave.grid.get((slot.y + 1) * width + slot.x);
+        slot.superposition.len() == 1
+            && fits(right, Direction::Right)
+            && fits(down, Direction::Down)
+    })
+}
+
+/// Run the wave until it collapses or fails.
+pub(crate) fn run<T>(wave: &mut Wave<T>) -> Result<(), String> {
+    while !wave.is_collapsed() {
+        wave.collapse(1)?;
+    }
+    Ok(())
+}
+
+/// Collapse the wave, starting over after each contradiction.
+/// Panics after `attempts` failures, so a regression fails the test instead of hanging it.
+pub(crate) fn solve<T>(wave: &mut Wave<T>, attempts: usize) {
+    for _ in 0..attempts {
+        wave.initialize();
+        if run(wave).is_ok() {
+            return;
+        }
+    }
+    panic!("No solution after {} attempts", attempts);
+}
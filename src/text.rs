@@ -0,0 +1,120 @@
+//! Draw a wave as text, for logs, tests and terminals.
+
+use std::fmt::Write;
+
+use crate::wave::Wave;
+
+/// Reset the ANSI color.
+const RESET: &str = "\x1b[0m";
+
+/// Draws every slot of a wave as a single character.
+/// ```
+/// use billow::{TextRenderer, Wave};
+/// # struct Tile;
+/// # impl billow::Image for Tile {
+/// #     fn size(&self) -> (usize, usize) { (1, 1) }
+/// #     fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] { [0; 4] }
+/// # }
+///
+/// let mut wave = Wave::new(&[Tile, Tile], 3, 2);
+/// wave.initialize();
+///
+/// let text = TextRenderer::new(vec!['─', '│']).render(&wave);
+/// assert_eq!(text.lines().count(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextRenderer {
+    /// Character of each module.
+    pub glyphs: Vec<char>,
+    /// Character of the slots with more than one possible module.
+    pub superposed: char,
+    /// Character of the slots without any possible module.
+    pub contradiction: char,
+    /// ANSI 256 color of each module. `None` to write plain text.
+    pub colors: Option<Vec<u8>>,
+}
+
+impl TextRenderer {
+    /// Create a renderer that writes `glyphs[i]` for module `i`, `?` for superposed slots and `!` for contradictions.
+    /// Modules without a glyph are written as `#`.
+    pub fn new(glyphs: Vec<char>) -> Self {
+        Self {
+            glyphs,
+            superposed: '?',
+            contradiction: '!',
+            colors: None,
+        }
+    }
+
+    /// Set the characters of superposed slots and contradictions.
+    pub fn markers(mut self, superposed: char, contradiction: char) -> Self {
+        self.superposed = superposed;
+        self.contradiction = contradiction;
+        self
+    }
+
+    /// Color each module with an ANSI 256 color. Superposed slots are yellow and contradictions red.
+    pub fn colors(mut self, colors: Vec<u8>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// Returns the wave as text, one line per row.
+    pub fn render<T>(&self, wave: &Wave<T>) -> String {
+        let mut text = String::new();
+        for row in wave.grid.chunks(wave.width().max(1)) {
+            for slot in row {
+                let (glyph, color) = match slot.superposition.as_slice() {
+                    [] => (self.contradiction, Some(9)),
+                    [module] => (
+                        self.glyphs.get(module.index).copied().unwrap_or('#'),
+                        self.colors
+                            .as_ref()
+                            .and_then(|colors| colors.get(module.index).copied()),
+                    ),
+                    _ => (self.superposed, Some(11)),
+                };
+                match color.filter(|_| self.colors.is_some()) {
+                    Some(color) => {
+                        let _ = write!(text, "\x1b[38;5;{}m{}{}", color, glyph, RESET);
+                    }
+                    None => text.push(glyph),
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// 2x2 wave with modules 0 and 1 on the first row, a superposed slot and a contradiction on the second.
+    fn wave() -> Wave {
+        testing::wave(2, 2, &[&[0], &[1], &[0, 1], &[]])
+    }
+
+    #[test]
+    fn test_render() {
+        let renderer = TextRenderer::new(vec!['.', '#']);
+        assert_eq!(renderer.render(&wave()), ".#\n?!\n");
+
+        let renderer = renderer.markers('~', 'x');
+        assert_eq!(renderer.render(&wave()), ".#\n~x\n");
+
+        let renderer = TextRenderer::new(vec!['a']);
+        assert_eq!(renderer.render(&wave()), "a#\n?!\n");
+    }
+
+    #[test]
+    fn test_colors() {
+        let renderer = TextRenderer::new(vec!['.', '#']).colors(vec![2]);
+        assert_eq!(
+            renderer.render(&wave()),
+            "\x1b[38;5;2m.\x1b[0m#\n\x1b[38;5;11m?\x1b[0m\x1b[38;5;9m!\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! Export of a collapsed wave as a [Tiled](https://www.mapeditor.org) map.
+//!
+//! The map has a single tile layer and an image collection tileset with one tile per source image.
+//! Rotated and mirrored variants are written with the flip flags of Tiled.
+//! Slots that are not collapsed are left empty.
+
+use std::{
+    ffi::OsString,
+    fmt::Write,
+    path::{Component, Path},
+};
+
+use crate::{escape, transform::Transform, wave::Wave};
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+
+/// Returns the Tiled flip flags of a transform.
+fn flags(transform: Transform) -> u32 {
+    let (diagonal, horizontal, vertical) = match (transform.rotation, transform.flip) {
+        (0, false) => (false, false, false),
+        (1, false) => (true, true, false),
+        (2, false) => (false, true, true),
+        (3, false) => (true, false, true),
+        (0, true) => (false, true, false),
+        (1, true) => (true, true, true),
+        (2, true) => (false, false, true),
+        _ => (true, false, false),
+    };
+    let flag = |set: bool, flag: u32| if set { flag } else { 0 };
+    flag(diagonal, FLIPPED_DIAGONALLY)
+        | flag(horizontal, FLIPPED_HORIZONTALLY)
+        | flag(vertical, FLIPPED_VERTICALLY)
+}
+
+/// Returns `path` relative to the directory `dir`, with `/` separators.
+/// Both are resolved from the current directory, without following symbolic links.
+fn relative_path(path: &Path, dir: &Path) -> String {
+    let absolute = |path: &Path| -> Option<Vec<OsString>> {
+        let path = if path.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            path
+        };
+        let path = std::path::absolute(path).ok()?;
+        let mut components = vec![];
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if matches!(components.last(), Some(Component::Normal(_))) {
+                        components.pop();
+                    }
+                }
+                _ => components.push(component),
+            }
+        }
+        Some(
+            components
+                .into_iter()
+                .map(|c| c.as_os_str().to_owned())
+                .collect(),
+        )
+    };
+    let (Some(target), Some(dir)) = (absolute(path), absolute(dir)) else {
+        return path.to_string_lossy().into_owned();
+    };
+    let common = target.iter().zip(&dir).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        // Different drives on Windows.
+        return path.to_string_lossy().into_owned();
+    }
+    let parents = dir[common..].iter().map(|_| String::from(".."));
+    let names = target[common..]
+        .iter()
+        .map(|name| name.to_string_lossy().into_owned());
+    parents.chain(names).collect::<Vec<_>>().join("/")
+}
+
+/// Describes how the modules of a wave are drawn in Tiled.
+/// ```
+/// use billow::{TiledMap, Transform};
+///
+/// // One image for each module, in the same order as the input of the wave.
+/// let map = TiledMap::new(vec!["blank.png".into(), "down.png".into()], (16, 16));
+///
+/// // Or an image and a transform for each module.
+/// let map = TiledMap::with_transforms(
+///     vec!["blank.png".into(), "down.png".into()],
+///     vec![(0, Transform::default()), (1, Transform::default()), (1, Transform::new(1, false))],
+///     (16, 16),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TiledMap {
+    /// Path of the images of the tileset, as written in the map.
+    pub images: Vec<String>,
+    /// Image and transform of each module.
+    pub modules: Vec<(usize, Transform)>,
+    /// Size of the tiles in pixels.
+    pub tile_size: (usize, usize),
+}
+
+impl TiledMap {
+    /// Create a map where each module is drawn with its own image.
+    pub fn new(images: Vec<String>, tile_size: (usize, usize)) -> Self {
+        let modules = (0..images.len())
+            .map(|idx| (idx, Transform::default()))
+            .collect();
+        Self::with_transforms(images, modules, tile_size)
+    }
+
+    /// Create a map where modules are transformed images.
+    pub fn with_transforms(
+        images: Vec<String>,
+        modules: Vec<(usize, Transform)>,
+        tile_size: (usize, usize),
+    ) -> Self {
+        Self {
+            images,
+            modules,
+            tile_size,
+        }
+    }
+
+    /// Create a map with the images of a tileset, to be written at `map_path`.
+    /// Image paths are written relative to the directory of the map, as Tiled expects.
+    #[cfg(feature = "tileset")]
+    pub fn from_tileset<T>(
+        tileset: &crate::tileset::Tileset<T>,
+        tile_size: (usize, usize),
+        map_path: impl AsRef<Path>,
+    ) -> Self {
+        let images = tileset
+            .definition
+            .tiles
+            .iter()
+            .map(|tile| match &tile.image {
+                Some(image) => tileset.base_dir.join(image).to_string_lossy().into_owned(),
+                None => String::new(),
+            })
+            .collect();
+        let modules = tileset
+            .variants
+            .iter()
+            .map(|variant| (variant.tile, variant.transform))
+            .collect();
+        Self::with_transforms(images, modules, tile_size).relative_to(map_path)
+    }
+
+    /// Rewrite the image paths, given relative to the current directory, relative to the directory
+    /// of the map file at `map_path`.
+    /// ```
+    /// use billow::TiledMap;
+    ///
+    /// let map = TiledMap::new(vec!["tiles/a.png".into()], (16, 16)).relative_to("maps/level.tmx");
+    /// assert_eq!(map.images, ["../tiles/a.png"]);
+    /// ```
+    pub fn relative_to(mut self, map_path: impl AsRef<Path>) -> Self {
+        let dir = map_path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        for image in self.images.iter_mut().filter(|image| !image.is_empty()) {
+            *image = relative_path(Path::new(image), dir);
+        }
+        self
+    }
+
+    /// Returns the global tile id of every slot, row by row. `0` for slots that are not collapsed.
+    pub fn tile_ids<T>(&self, wave: &Wave<T>) -> Vec<u32> {
+        wave.grid
+            .iter()
+            .map(|slot| match slot.superposition.as_slice() {
+                [module] => match self.modules.get(module.index) {
+                    Some(&(image, transform)) => (image as u32 + 1) | flags(transform),
+                    None => 0,
+                },
+                _ => 0,
+            })
+            .collect()
+    }
+
+    /// Write the wave as a TMX map.
+    pub fn to_tmx<T>(&self, wave: &Wave<T>) -> String {
+        let (tw, th) = self.tile_size;
+        let mut tmx = String::new();
+
+        tmx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            tmx,
+            "<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{}\" height=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" infinite=\"0\" nextlayerid=\"2\" nextobjectid=\"1\">",
+            wave.width(),
+            wave.height(),
+            tw,
+            th
+        );
+        let _ = writeln!(
+            tmx,
+            " <tileset firstgid=\"1\" name=\"billow\" tilewidth=\"{}\" tileheight=\"{}\" tilecount=\"{}\" columns=\"0\">",
+            tw,
+            th,
+            self.images.len()
+        );
+        tmx.push_str("  <grid orientation=\"orthogonal\" width=\"1\" height=\"1\"/>\n");
+        for (id, image) in self.images.iter().enumerate() {
+            let _ = writeln!(tmx, "  <tile id=\"{}\">", id);
+            let _ = writeln!(
+                tmx,
+                "   <image width=\"{}\" height=\"{}\" source=\"{}\"/>",
+                tw,
+                th,
+                escape::xml(image)
+            );
+            tmx.push_str("  </tile>\n");
+        }
+        tmx.push_str(" </tileset>\n");
+
+        let _ = writeln!(
+            tmx,
+            " <layer id=\"1\" name=\"wave\" width=\"{}\" height=\"{}\">",
+            wave.width(),
+            wave.height()
+        );
+        tmx.push_str("  <data encoding=\"csv\">\n");
+        let ids = self.tile_ids(wave);
+        let rows: Vec<String> = ids
+            .chunks(wave.width().max(1))
+            .map(|row| {
+                row.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect();
+        tmx.push_str(&rows.join(",\n"));
+        tmx.push_str("\n  </data>\n </layer>\n</map>\n");
+        tmx
+    }
+
+    /// Write the wave as a Tiled JSON map.
+    pub fn to_json<T>(&self, wave: &Wave<T>) -> String {
+        let (tw, th) = self.tile_size;
+        let tiles: Vec<String> = self
+            .images
+            .iter()
+            .enumerate()
+            .map(|(id, image)| {
+                format!(
+                    "{{\"id\":{},\"image\":\"{}\",\"imagewidth\":{},\"imageheight\":{}}}",
+                    id,
+                    escape::json(image),
+                    tw,
+                    th
+                )
+            })
+            .collect();
+        let data: Vec<String> = self
+            .tile_ids(wave)
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+
+        format!(
+            concat!(
+                "{{\"type\":\"map\",\"version\":\"1.10\",\"orientation\":\"orthogonal\",",
+                "\"renderorder\":\"right-down\",\"infinite\":false,\"width\":{w},\"height\":{h},",
+                "\"tilewidth\":{tw},\"tileheight\":{th},\"nextlayerid\":2,\"nextobjectid\":1,",
+                "\"layers\":[{{\"id\":1,\"name\":\"wave\",\"type\":\"tilelayer\",\"x\":0,\"y\":0,",
+                "\"width\":{w},\"height\":{h},\"opacity\":1,\"visible\":true,\"data\":[{data}]}}],",
+                "\"tilesets\":[{{\"firstgid\":1,\"name\":\"billow\",\"tilewidth\":{tw},\"tileheight\":{th},",
+                "\"tilecount\":{count},\"columns\":0,\"margin\":0,\"spacing\":0,",
+                "\"grid\":{{\"orientation\":\"orthogonal\",\"width\":1,\"height\":1}},\"tiles\":[{tiles}]}}]}}"
+            ),
+            w = wave.width(),
+            h = wave.height(),
+            tw = tw,
+            th = th,
+            data = data.join(","),
+            count = self.images.len(),
+            tiles = tiles.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// 2x1 wave with the first module on the left and the second on the right.
+    fn wave() -> Wave {
+        testing::wave(2, 1, &[&[0], &[1]])
+    }
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(flags(Transform::new(0, false)), 0);
+        assert_eq!(
+            flags(Transform::new(1, false)),
+            FLIPPED_DIAGONALLY | FLIPPED_HORIZONTALLY
+        );
+        assert_eq!(
+            flags(Transform::new(2, false)),
+            FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY
+        );
+        assert_eq!(
+            flags(Transform::new(3, false)),
+            FLIPPED_DIAGONALLY | FLIPPED_VERTICALLY
+        );
+        assert_eq!(flags(Transform::new(0, true)), FLIPPED_HORIZONTALLY);
+        assert_eq!(flags(Transform::new(3, true)), FLIPPED_DIAGONALLY);
+    }
+
+    #[test]
+    fn test_tile_ids() {
+        let mut wave = wave();
+        let map = TiledMap::with_transforms(
+            vec!["a.png".into()],
+            vec![(0, Transform::default()), (0, Transform::new(2, false))],
+            (3, 3),
+        );
+        assert_eq!(
+            map.tile_ids(&wave),
+            vec![1, 1 | FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY]
+        );
+
+        wave.grid[1].superposition.clear();
+        assert_eq!(map.tile_ids(&wave), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_tmx() {
+        let map = TiledMap::new(vec!["a.png".into(), "b&c.png".into()], (3, 3));
+        let tmx = map.to_tmx(&wave());
+
+        assert!(tmx.contains("width=\"2\" height=\"1\" tilewidth=\"3\" tileheight=\"3\""));
+        assert!(tmx.contains("<image width=\"3\" height=\"3\" source=\"b&amp;c.png\"/>"));
+        assert!(tmx.contains("<data encoding=\"csv\">\n1,2\n  </data>"));
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let relative = |path: &str, dir: &str| relative_path(Path::new(path), Path::new(dir));
+        assert_eq!(relative("tiles/a.png", ""), "tiles/a.png");
+        assert_eq!(relative("tiles/a.png", "maps"), "../tiles/a.png");
+        assert_eq!(relative("tiles/a.png", "tiles"), "a.png");
+        assert_eq!(
+            relative("a/../tiles/./a.png", "out/maps/.."),
+            "../tiles/a.png"
+        );
+
+        let absolute = std::path::absolute("tiles/a.png").unwrap();
+        assert_eq!(
+            relative(absolute.to_str().unwrap(), "maps"),
+            "../tiles/a.png"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let map = TiledMap::new(vec!["a.png".into(), "b\"c\n.png".into()], (3, 3));
+        let json = map.to_json(&wave());
+
+        assert!(json.contains("\"data\":[1,2]"));
+        assert!(json.contains("\"image\":\"b\\\"c\\n.png\""));
+        assert!(serde_json::from_str::<serde_json::Value>(&json).is_ok());
+    }
+}
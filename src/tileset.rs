@@ -0,0 +1,843 @@
+//! Tilesets described in a JSON file instead of code.
+//!
+//! Every tile has a name and optionally an image, a symmetry, a weight and explicit connectors.
+//! Sides without an explicit connector are sampled from the image, with the default constraint function
+//! unless `edges` sets an [`EdgeConstraint`].
+//! A side can also be a list of sockets, see [`Sockets`]. Image sides are split in `sockets` parts, 1 by default.
+//! When neighbor rules are given, they replace the connectors to decide which modules fit together.
+//! A rule also applies to the variants obtained by rotating or mirroring both tiles together,
+//! unless `exact_neighbors` is set. The connectors are still used by the connectivity and path
+//! constraints, so tiles with neighbor rules should keep connectors that agree with them.
+//!
+//! The symmetry follows the notation of the original WaveFunctionCollapse and creates rotated variants
+//! of the tile, named `"<name> <variant>"`. The first variant can also be referred to by the tile name.
+//!
+//! ```json
+//! {
+//!     "tiles": [
+//!         { "name": "blank", "image": "blank.png" },
+//!         { "name": "down", "image": "down.png", "symmetry": "T", "weight": 0.5 },
+//!         {
+//!             "name": "bridge",
+//!             "symmetry": "I",
+//!             "connectors": { "up": "road", "right": "water", "down": "road", "left": "water" }
+//!         },
+//!         {
+//!             "name": "lanes",
+//!             "connectors": { "up": ["grass", "road", "road"], "right": "grass", "down": ["road", "road", "grass"], "left": "grass" }
+//!         }
+//!     ],
+//!     "neighbors": [{ "tile": "bridge", "direction": "up", "neighbor": "down 2" }],
+//!     "edges": { "sampling": "full", "depth": 2, "matching": { "tolerance": 20.0 } }
+//! }
+//! ```
+//!
+//! The definition types implement `serde::Deserialize`, so other formats can be parsed with their own crate
+//! and loaded with [`Tileset::from_definition`].
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    conector::{Collision, ConnectorID, ConnectorRegistry},
+    direction::Direction,
+    edge::EdgeConstraint,
+    module::Module,
+    socket::{side_connector, socket_fn, Sockets},
+    transform::Transform,
+    wave::Wave,
+};
+
+/// Contents of a tileset file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TilesetDefinition {
+    /// Tiles of the set.
+    pub tiles: Vec<TileDefinition>,
+    /// Allowed neighbors. When empty, the connectors are used instead.
+    #[serde(default)]
+    pub neighbors: Vec<NeighborRule>,
+    /// Apply the neighbor rules only to the variants they name, instead of also to the pair
+    /// rotated and mirrored by every transform.
+    #[serde(default)]
+    pub exact_neighbors: bool,
+    /// How the connectors are sampled from the images.
+    #[serde(default)]
+    pub edges: EdgeConstraint,
+    /// Number of sockets sampled on each side of the images.
+    #[serde(default = "default_sockets")]
+    pub sockets: usize,
+}
+
+/// A tile of the tileset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileDefinition {
+    /// Unique name of the tile.
+    pub name: String,
+    /// Path to the image, relative to the tileset file.
+    #[serde(default)]
+    pub image: Option<PathBuf>,
+    /// Symmetry of the tile: `X`, `I`, `\`, `L`, `T` or `F`.
+    #[serde(default = "default_symmetry")]
+    pub symmetry: String,
+    /// Relative probability of the tile. Shared by all of its variants.
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// Explicit connectors for the unrotated tile.
+    #[serde(default)]
+    pub connectors: ConnectorsDefinition,
+}
+
+/// Connector names of each side of a tile.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConnectorsDefinition {
+    pub up: Option<SideDefinition>,
+    pub right: Option<SideDefinition>,
+    pub down: Option<SideDefinition>,
+    pub left: Option<SideDefinition>,
+}
+
+/// A side with a single connector, or with several sockets listed clockwise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SideDefinition {
+    Connector(String),
+    Sockets(Vec<String>),
+}
+
+impl SideDefinition {
+    /// Returns the names of the sockets of the side.
+    fn names(&self) -> &[String] {
+        match self {
+            SideDefinition::Connector(name) => std::slice::from_ref(name),
+            SideDefinition::Sockets(names) => names,
+        }
+    }
+}
+
+/// Allows `neighbor` to be placed in `direction` of `tile`, and `tile` in the opposite direction of `neighbor`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NeighborRule {
+    pub tile: String,
+    pub direction: Direction,
+    pub neighbor: String,
+}
+
+fn default_symmetry() -> String {
+    String::from("X")
+}
+
+fn default_sockets() -> usize {
+    1
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Error found while loading a tileset.
+#[derive(Debug)]
+pub enum TilesetError {
+    /// The file could not be read.
+    Io(std::io::Error),
+    /// The file is not a valid tileset definition.
+    Parse(String),
+    /// The tileset has no tiles.
+    Empty,
+    /// A tile, or a rule referring to it, is invalid.
+    Tile { name: String, reason: String },
+    /// Two different connectors have the same ID.
+    Collision(Collision),
+}
+
+impl TilesetError {
+    pub(crate) fn tile(name: &str, reason: impl Into<String>) -> Self {
+        TilesetError::Tile {
+            name: name.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for TilesetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TilesetError::Io(err) => write!(f, "Failed to read tileset: {}", err),
+            TilesetError::Parse(err) => write!(f, "Invalid tileset: {}", err),
+            TilesetError::Empty => write!(f, "The tileset has no tiles"),
+            TilesetError::Tile { name, reason } => write!(f, "Tile \"{}\": {}", name, reason),
+            TilesetError::Collision(collision) => write!(
+                f,
+                "Connector {} is shared by {}",
+                collision.id,
+                collision.sources.join(" and ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TilesetError {}
+
+/// Returns the transforms of the variants of a tile with the given symmetry.
+pub fn symmetry_variants(symmetry: &str) -> Option<Vec<Transform>> {
+    let rotations = |count: u8| (0..count).map(|r| Transform::new(r, false));
+    match symmetry {
+        "X" => Some(rotations(1).collect()),
+        "I" | "\\" => Some(rotations(2).collect()),
+        "L" | "T" => Some(rotations(4).collect()),
+        "F" => Some(
+            rotations(4)
+                .chain((0..4).map(|r| Transform::new(r, true)))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Sides of a tile with the given symmetry, chosen so that two transforms give the same sides
+/// exactly when they give the same variant.
+fn symmetry_shape(symmetry: &str) -> [[u8; 2]; 4] {
+    match symmetry {
+        "I" => [[1, 1], [0, 0], [1, 1], [0, 0]],
+        "\\" => [[1, 2], [2, 1], [1, 2], [2, 1]],
+        "L" => [[1, 1], [1, 1], [0, 0], [0, 0]],
+        "T" => [[0, 0], [1, 1], [1, 1], [1, 1]],
+        "F" => [[1, 2], [3, 4], [5, 6], [7, 8]],
+        _ => [[0, 0]; 4],
+    }
+}
+
+/// Returns the sides after a transform. Mirroring reverses the clockwise order of each side.
+fn transform_sides(transform: &Transform, sides: [[u8; 2]; 4]) -> [[u8; 2]; 4] {
+    let mut sides = transform.connectors(sides);
+    if transform.flip {
+        sides.iter_mut().for_each(|side| side.reverse());
+    }
+    sides
+}
+
+/// A variant of a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Variant {
+    /// Position of the tile in the definition.
+    pub tile: usize,
+    /// Orientation of the variant.
+    pub transform: Transform,
+}
+
+/// Modules built from a tileset definition, each with a payload of type `T`.
+pub struct Tileset<T = ()> {
+    /// Name of each module.
+    pub names: Vec<String>,
+    /// Tile and orientation of each module.
+    pub variants: Vec<Variant>,
+    /// Definition the tileset was built from.
+    pub definition: TilesetDefinition,
+    /// Directory the image paths are relative to.
+    pub base_dir: PathBuf,
+    /// Connector names and sampled pixels of the modules.
+    pub registry: ConnectorRegistry,
+    modules: Vec<Module>,
+    /// Payload of each module.
+    payloads: Vec<T>,
+    /// Allowed modules for each module and direction. `None` to use the connectors.
+    rules: Option<Vec<[Vec<bool>; 4]>>,
+    /// Sockets of the modules, when a side has more than one.
+    sockets: Option<Sockets>,
+    #[cfg(feature = "image")]
+    images: Vec<Option<image::DynamicImage>>,
+}
+
+impl Tileset {
+    /// Load a tileset file. Image paths are relative to the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TilesetError> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(TilesetError::Io)?;
+        Tileset::from_json(&json, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    /// Parse a tileset definition. Image paths are relative to `base_dir`.
+    pub fn from_json(json: &str, base_dir: impl AsRef<Path>) -> Result<Self, TilesetError> {
+        let definition =
+            serde_json::from_str(json).map_err(|e| TilesetError::Parse(e.to_string()))?;
+        Tileset::from_definition(definition, base_dir)
+    }
+
+    /// Validate the definition and build the modules of every variant.
+    pub fn from_definition(
+        definition: TilesetDefinition,
+        base_dir: impl AsRef<Path>,
+    ) -> Result<Self, TilesetError> {
+        if definition.tiles.is_empty() {
+            return Err(TilesetError::Empty);
+        }
+
+        let registry = ConnectorRegistry::new();
+        let socket_fn = socket_fn(
+            definition.edges.build_registered(&registry),
+            definition.sockets,
+        );
+        let mut names = vec![];
+        let mut variants = vec![];
+        let mut modules = vec![];
+        let mut sides = vec![];
+        let mut lookup = HashMap::new();
+        #[cfg(feature = "image")]
+        let mut images = vec![];
+
+        for (idx, tile) in definition.tiles.iter().enumerate() {
+            if lookup.contains_key(&tile.name) {
+                return Err(TilesetError::tile(&tile.name, "duplicated name"));
+            }
+            if !tile.weight.is_finite() || tile.weight <= 0.0 {
+                return Err(TilesetError::tile(&tile.name, "weight must be positive"));
+            }
+            let transforms = symmetry_variants(&tile.symmetry).ok_or_else(|| {
+                TilesetError::tile(&tile.name, format!("unknown symmetry {}", tile.symmetry))
+            })?;
+
+            let image = load_image(tile, base_dir.as_ref())?;
+            let explicit = [
+                &tile.connectors.up,
+                &tile.connectors.right,
+                &tile.connectors.down,
+                &tile.connectors.left,
+            ]
+            .map(|side| {
+                side.as_ref().map(|side| {
+                    let sockets = side.names().iter().map(|name| registry.register(name));
+                    sockets.collect::<Vec<_>>()
+                })
+            });
+
+            for (variant, transform) in transforms.into_iter().enumerate() {
+                let mut module = Module::new(modules.len());
+                module.weight = tile.weight;
+
+                let mut explicit = transform.connectors(explicit.clone());
+                if transform.flip {
+                    // Mirroring reverses the clockwise order of the sockets.
+                    explicit
+                        .iter_mut()
+                        .flatten()
+                        .for_each(|side| side.reverse());
+                }
+                let mut module_sides: [Vec<ConnectorID>; 4] = Default::default();
+                for direction in Direction::all() {
+                    let side = match &explicit[direction as usize] {
+                        Some(sockets) => sockets.clone(),
+                        None => match &image {
+                            Some(image) => socket_fn(&transform.apply(image), direction),
+                            None => {
+                                return Err(TilesetError::tile(
+                                    &tile.name,
+                                    format!("no connector {} and no image", direction),
+                                ))
+                            }
+                        },
+                    };
+                    module.connectors[direction as usize] = side_connector(&side, direction);
+                    module_sides[direction as usize] = side;
+                }
+                sides.push(module_sides);
+
+                let name = format!("{} {}", tile.name, variant);
+                if variant == 0 {
+                    lookup.insert(tile.name.clone(), module.index);
+                }
+                lookup.insert(name.clone(), module.index);
+                names.push(if variant == 0 {
+                    tile.name.clone()
+                } else {
+                    name
+                });
+                variants.push(Variant {
+                    tile: idx,
+                    transform,
+                });
+                modules.push(module);
+            }
+
+            #[cfg(feature = "image")]
+            images.push(image);
+        }
+
+        if let Some(collision) = registry.collisions().into_iter().next() {
+            return Err(TilesetError::Collision(collision));
+        }
+
+        let rules = if definition.neighbors.is_empty() {
+            None
+        } else {
+            let find = |name: &String| {
+                lookup
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| TilesetError::tile(name, "unknown tile in neighbor rule"))
+            };
+
+            // Returns the module showing `module` with `transform` applied on top of its own.
+            let orient = |module: usize, transform: &Transform| {
+                let variant = variants[module];
+                let shape = symmetry_shape(&definition.tiles[variant.tile].symmetry);
+                let sides = transform_sides(transform, transform_sides(&variant.transform, shape));
+                variants
+                    .iter()
+                    .position(|v| {
+                        v.tile == variant.tile && transform_sides(&v.transform, shape) == sides
+                    })
+                    .expect("The variants of a symmetry are closed under its transforms")
+            };
+
+            let empty: [Vec<bool>; 4] = std::array::from_fn(|_| vec![false; modules.len()]);
+            let mut rules = vec![empty; modules.len()];
+            let directions = [
+                Direction::Up,
+                Direction::Right,
+                Direction::Down,
+                Direction::Left,
+            ];
+            for rule in &definition.neighbors {
+                let (tile, neighbor) = (find(&rule.tile)?, find(&rule.neighbor)?);
+                // A rule also holds for the pair rotated or mirrored as a whole.
+                let transforms = if definition.exact_neighbors {
+                    vec![Transform::default()]
+                } else {
+                    symmetry_variants("F").expect("F is a symmetry")
+                };
+                for transform in transforms {
+                    let (tile, neighbor) = (orient(tile, &transform), orient(neighbor, &transform));
+                    let direction = transform
+                        .connectors(directions)
+                        .iter()
+                        .position(|d| *d == rule.direction)
+                        .expect("Every direction has a side");
+                    rules[tile][direction][neighbor] = true;
+                    rules[neighbor][directions[direction].reverse() as usize][tile] = true;
+                }
+            }
+            Some(rules)
+        };
+        let several = |side: &Vec<ConnectorID>| side.len() > 1;
+        let sockets = if sides.iter().flatten().any(several) {
+            Some(Sockets::new(sides))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            names,
+            variants,
+            definition,
+            base_dir: base_dir.as_ref().to_path_buf(),
+            registry,
+            payloads: vec![(); modules.len()],
+            modules,
+            rules,
+            sockets,
+            #[cfg(feature = "image")]
+            images,
+        })
+    }
+}
+
+impl<T> Tileset<T> {
+    /// Attach a payload to every module, computed from its name and variant.
+    /// ```
+    /// use billow::Tileset;
+    ///
+    /// let json = r#"{ "tiles": [{ "name": "a", "connectors": { "up": "x", "right": "x", "down": "x", "left": "x" } }] }"#;
+    /// let tileset = Tileset::from_json(json, "")
+    ///     .unwrap()
+    ///     .with_payloads(|name, variant| format!("prefab/{}/{}", name, variant.transform.rotation));
+    /// assert_eq!(tileset.payload(0), Some(&String::from("prefab/a/0")));
+    /// ```
+    pub fn with_payloads<U>(self, mut payload: impl FnMut(&str, &Variant) -> U) -> Tileset<U> {
+        let payloads = self
+            .names
+            .iter()
+            .zip(&self.variants)
+            .map(|(name, variant)| payload(name, variant))
+            .collect();
+        Tileset {
+            names: self.names,
+            variants: self.variants,
+            definition: self.definition,
+            base_dir: self.base_dir,
+            registry: self.registry,
+            modules: self.modules,
+            payloads,
+            rules: self.rules,
+            sockets: self.sockets,
+            #[cfg(feature = "image")]
+            images: self.images,
+        }
+    }
+
+    /// Returns the payload of a module.
+    pub fn payload(&self, module: usize) -> Option<&T> {
+        self.payloads.get(module)
+    }
+
+    /// Number of modules, counting every variant.
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    /// Returns true if the tileset has no modules.
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Returns the index of the module with the given name, as used by the constraints.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name).or_else(|| {
+            name.strip_suffix(" 0")
+                .and_then(|base| self.names.iter().position(|n| n == base))
+        })
+    }
+
+    /// Create a new wave collapse function with the modules and payloads of the tileset.
+    pub fn wave(&self, width: usize, height: usize) -> Wave<T>
+    where
+        T: Clone,
+    {
+        let mut wave = Wave::from_modules(self.modules.clone(), width, height)
+            .with_payloads(self.payloads.clone())
+            .expect("One payload per module");
+        self.restore(&mut wave);
+        wave
+    }
+
+    /// Attach the rules and connector names of the tileset to a wave created by [`Tileset::wave`].
+    /// They are not saved with the wave, so call this after loading it.
+    pub fn restore(&self, wave: &mut Wave<T>) {
+        wave.set_registry(self.registry.clone());
+        if let Some(rules) = self.rules.clone() {
+            wave.is_possible_fn = Arc::new(move |module, from, _to, direction| {
+                from.superposition
+                    .iter()
+                    .any(|m| rules[m.index][direction as usize][module.index])
+            });
+        } else if let Some(sockets) = self.sockets.clone() {
+            wave.set_sockets(sockets);
+        }
+    }
+
+    /// Returns the image of a module, with its variant transform applied.
+    #[cfg(feature = "image")]
+    pub fn image(&self, module: usize) -> Option<image::RgbaImage> {
+        use crate::wave::Image;
+
+        let variant = self.variants.get(module)?;
+        let source = self.images[variant.tile].as_ref()?;
+        let image = variant.transform.apply(source);
+        let (w, h) = image.size();
+        Some(image::RgbaImage::from_fn(w as u32, h as u32, |x, y| {
+            image::Rgba(image.get_pixel_at(x as usize, y as usize))
+        }))
+    }
+
+    /// Returns an image with the tile of every module, row by row.
+    /// Modules without image are left transparent.
+    #[cfg(feature = "image")]
+    pub fn atlas(&self, columns: usize, tile_size: usize) -> image::RgbaImage {
+        let columns = columns.max(1);
+        let rows = self.len().div_ceil(columns);
+        let mut atlas =
+            image::RgbaImage::new((columns * tile_size) as u32, (rows * tile_size) as u32);
+        for module in 0..self.len() {
+            if let Some(tile) = self.image(module) {
+                let (x, y) = (module % columns * tile_size, module / columns * tile_size);
+                image::imageops::replace(&mut atlas, &tile, x as i64, y as i64);
+            }
+        }
+        atlas
+    }
+}
+
+#[cfg(feature = "image")]
+fn load_image(
+    tile: &TileDefinition,
+    base_dir: &Path,
+) -> Result<Option<image::DynamicImage>, TilesetError> {
+    match &tile.image {
+        Some(path) => image::open(base_dir.join(path))
+            .map(Some)
+            .map_err(|err| TilesetError::tile(&tile.name, err.to_string())),
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "image"))]
+fn load_image(tile: &TileDefinition, _base_dir: &Path) -> Result<Option<NoImage>, TilesetError> {
+    match &tile.image {
+        Some(_) => Err(TilesetError::tile(
+            &tile.name,
+            "images require the `image` feature",
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Placeholder for tiles without image when the `image` feature is disabled.
+#[cfg(not(feature = "image"))]
+struct NoImage;
+
+#[cfg(not(feature = "image"))]
+impl crate::wave::Image for NoImage {
+    fn size(&self) -> (usize, usize) {
+        (0, 0)
+    }
+    fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] {
+        [0; 4]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        edge::{EdgeBand, EdgeMatching, EdgeSampling},
+        testing,
+        wave::get_constraint_fn,
+    };
+
+    const ROADS: &str = r#"{
+        "tiles": [
+            {
+                "name": "grass",
+                "connectors": { "up": "grass", "right": "grass", "down": "grass", "left": "grass" }
+            },
+            {
+                "name": "road",
+                "symmetry": "I",
+                "weight": 2.0,
+                "connectors": { "up": "road", "right": "grass", "down": "road", "left": "grass" }
+            },
+            {
+                "name": "turn",
+                "symmetry": "L",
+                "connectors": { "up": "road", "right": "road", "down": "grass", "left": "grass" }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_variants() {
+        let tileset = Tileset::from_json(ROADS, "").unwrap();
+        assert_eq!(tileset.len(), 7);
+        assert_eq!(tileset.names[..4], ["grass", "road", "road 1", "turn"]);
+        assert_eq!(tileset.index("road 0"), Some(1));
+        assert_eq!(tileset.index("turn 3"), Some(6));
+        assert_eq!(tileset.modules[2].weight, 2.0);
+
+        let road = ConnectorID::from("road");
+        let grass = ConnectorID::from("grass");
+        assert_eq!(tileset.modules[2].connectors, [grass, road, grass, road]);
+        assert_eq!(tileset.modules[4].connectors, [grass, road, road, grass]);
+    }
+
+    #[test]
+    fn test_wave() {
+        let tileset = Tileset::from_json(ROADS, "").unwrap();
+        let mut wave = tileset.wave(8, 8);
+        testing::solve(&mut wave, 100);
+
+        // Explicit connectors keep their name.
+        let connector = wave.grid[0].superposition[0].connectors[0];
+        assert!(["grass", "road"].contains(&wave.connector_name(connector).as_str()));
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let json = r#"{
+            "tiles": [{ "name": "a" , "connectors": { "up": "x", "right": "x", "down": "x", "left": "x" } }],
+            "neighbors": [{ "tile": "a", "direction": "right", "neighbor": "b" }]
+        }"#;
+        let err = Tileset::from_json(json, "").err().unwrap();
+        assert_eq!(err.to_string(), "Tile \"b\": unknown tile in neighbor rule");
+
+        let json = r#"{
+            "tiles": [
+                { "name": "a", "connectors": { "up": "x", "right": "x", "down": "x", "left": "x" } },
+                { "name": "b", "symmetry": "L", "connectors": { "up": "x", "right": "x", "down": "x", "left": "x" } }
+            ],
+            "neighbors": [{ "tile": "a", "direction": "right", "neighbor": "b" }]
+        }"#;
+        let allowed = |tileset: &Tileset| {
+            let rules = tileset.rules.as_ref().unwrap();
+            let mut allowed = vec![];
+            for direction in Direction::all() {
+                let side = rules[0][direction as usize].iter().zip(&tileset.names);
+                for (_, name) in side.skip(1).filter(|(allowed, _)| **allowed) {
+                    allowed.push(format!("{} {}", direction, name));
+                }
+            }
+            allowed
+        };
+
+        // Rotating the pair turns the rule around `a`, mirroring it also puts `b 3` on the left.
+        let tileset = Tileset::from_json(json, "").unwrap();
+        assert_eq!(
+            allowed(&tileset),
+            [
+                "up b",
+                "up b 3",
+                "right b",
+                "right b 1",
+                "down b 1",
+                "down b 2",
+                "left b 2",
+                "left b 3",
+            ]
+        );
+        let rules = tileset.rules.as_ref().unwrap();
+        assert!(rules[2][Direction::Up as usize][0]);
+
+        let mut definition: TilesetDefinition = serde_json::from_str(json).unwrap();
+        definition.exact_neighbors = true;
+        let tileset = Tileset::from_definition(definition, "").unwrap();
+        assert_eq!(allowed(&tileset), ["right b"]);
+    }
+
+    #[test]
+    fn test_restore() {
+        // Two `b` can not be side by side, which the connectors alone allow.
+        let json = r#"{
+            "tiles": [
+                { "name": "a", "connectors": { "up": "x", "right": "x", "down": "x", "left": "x" } },
+                { "name": "b", "connectors": { "up": "x", "right": "x", "down": "x", "left": "x" } }
+            ],
+            "neighbors": [
+                { "tile": "a", "direction": "right", "neighbor": "a" },
+                { "tile": "a", "direction": "right", "neighbor": "b" },
+                { "tile": "b", "direction": "right", "neighbor": "a" },
+                { "tile": "a", "direction": "up", "neighbor": "a" },
+                { "tile": "a", "direction": "up", "neighbor": "b" },
+                { "tile": "b", "direction": "up", "neighbor": "a" },
+                { "tile": "b", "direction": "up", "neighbor": "b" }
+            ],
+            "exact_neighbors": true
+        }"#;
+        let tileset = Tileset::from_json(json, "").unwrap();
+        let mut wave = tileset.wave(8, 8);
+        wave.set_seed(3);
+        wave.initialize();
+        wave.collapse(10).expect("Failed to collapse");
+
+        let saved = serde_json::to_string(&wave).unwrap();
+        let mut resumed: Wave = serde_json::from_str(&saved).unwrap();
+        assert!(resumed.collapse(1).is_err());
+        tileset.restore(&mut resumed);
+
+        testing::run(&mut wave).expect("Failed to collapse");
+        testing::run(&mut resumed).expect("Failed to collapse");
+        let indices = |wave: &Wave| -> Vec<usize> {
+            wave.grid
+                .iter()
+                .map(|slot| slot.superposition[0].index)
+                .collect()
+        };
+        assert_eq!(indices(&wave), indices(&resumed));
+        assert!(indices(&resumed)
+            .chunks(8)
+            .all(|row| row.windows(2).all(|pair| pair != [1, 1])));
+    }
+
+    #[test]
+    fn test_edges() {
+        let json = r#"{
+            "tiles": [{ "name": "a" , "connectors": { "up": "x", "right": "x", "down": "x", "left": "x" } }],
+            "edges": { "sampling": { "points": 5 }, "band": "aligned", "matching": { "quantize": 8 } }
+        }"#;
+        let tileset = Tileset::from_json(json, "").unwrap();
+        assert_eq!(
+            tileset.definition.edges,
+            EdgeConstraint::new()
+                .sampling(EdgeSampling::Points(5))
+                .band(EdgeBand::Aligned)
+                .matching(EdgeMatching::Quantize(8))
+        );
+    }
+
+    #[test]
+    fn test_sockets() {
+        let json = r#"{
+            "tiles": [{
+                "name": "a",
+                "symmetry": "F",
+                "connectors": { "up": ["x", "y", "z"], "right": "w", "down": ["z", "y", "x"], "left": "w" }
+            }]
+        }"#;
+        let tileset = Tileset::from_json(json, "").unwrap();
+        let [x, y, z] = ["x", "y", "z"].map(ConnectorID::from);
+        let sockets = tileset.sockets.as_ref().unwrap();
+        assert_eq!(sockets.sides[0][Direction::Down as usize], vec![z, y, x]);
+        // Mirrored variant.
+        assert_eq!(sockets.sides[4][Direction::Up as usize], vec![z, y, x]);
+        assert_eq!(
+            sockets.sides[4][Direction::Right as usize],
+            vec![ConnectorID::from("w")]
+        );
+
+        let connectors = tileset.modules[0].connectors;
+        assert_eq!(
+            connectors[Direction::Up as usize],
+            connectors[Direction::Down as usize]
+        );
+        assert!(sockets.fits(0, 0, Direction::Up));
+        assert!(!sockets.fits(0, 4, Direction::Up));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |json: &str| Tileset::from_json(json, "").err().unwrap().to_string();
+
+        assert_eq!(error(r#"{ "tiles": [] }"#), "The tileset has no tiles");
+        assert_eq!(
+            error(r#"{ "tiles": [{ "name": "a", "symmetry": "Q" }] }"#),
+            "Tile \"a\": unknown symmetry Q"
+        );
+        assert_eq!(
+            error(r#"{ "tiles": [{ "name": "a", "connectors": { "up": "x" } }] }"#),
+            "Tile \"a\": no connector right and no image"
+        );
+        assert_eq!(
+            error(r#"{ "tiles": [{ "name": "a", "weight": 0 }] }"#),
+            "Tile \"a\": weight must be positive"
+        );
+        assert!(error(r#"{ "tiles": [{ "title": "a" }] }"#).starts_with("Invalid tileset"));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_load() {
+        let tileset = Tileset::load("assets/basic/tileset.json").unwrap();
+        assert_eq!(tileset.len(), 5);
+
+        // Rotating "down" gives the other tiles of the folder.
+        let constraint_fn = get_constraint_fn(3);
+        let left = image::open("assets/basic/left.png").unwrap();
+        for direction in Direction::all() {
+            assert_eq!(
+                tileset.modules[2].connectors[direction as usize],
+                constraint_fn(&left, direction)
+            );
+        }
+        assert_eq!(
+            tileset.image(2).unwrap().dimensions(),
+            left.to_rgba8().dimensions()
+        );
+
+        let size = left.width();
+        let atlas = tileset.atlas(2, size as usize);
+        assert_eq!(atlas.dimensions(), (2 * size, 3 * size));
+        assert_eq!(*atlas.get_pixel(0, size), tileset.image(2).unwrap()[(0, 0)]);
+    }
+}
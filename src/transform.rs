@@ -0,0 +1,158 @@
+use crate::{pixel::PixelFormat, wave::Image};
+
+/// Orientation of a module relative to its source image.
+///
+/// The image is mirrored horizontally first, then rotated clockwise.
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    /// Number of clockwise quarter turns, from 0 to 3.
+    pub rotation: u8,
+    /// Mirrored horizontally.
+    pub flip: bool,
+}
+
+impl Transform {
+    /// Create a new transform. The rotation wraps around after 3 quarter turns.
+    pub fn new(rotation: u8, flip: bool) -> Self {
+        Self {
+            rotation: rotation % 4,
+            flip,
+        }
+    }
+
+    /// Returns the sides of the transformed module, in the order of `Direction::all`.
+    /// ```
+    /// use billow::{ConnectorID, Transform};
+    ///
+    /// let [a, b, c, d] = ["a", "b", "c", "d"].map(ConnectorID::from);
+    /// assert_eq!(Transform::new(1, false).connectors([a, b, c, d]), [d, a, b, c]);
+    /// assert_eq!(Transform::new(0, true).connectors([a, b, c, d]), [a, d, c, b]);
+    /// ```
+    pub fn connectors<T>(&self, sides: [T; 4]) -> [T; 4] {
+        let mut result = sides;
+        if self.flip {
+            result.swap(1, 3);
+        }
+        result.rotate_right(self.rotation as usize);
+        result
+    }
+
+    /// Returns a view of the image with the transform applied.
+    pub fn apply<'a, I: Image + ?Sized>(&self, image: &'a I) -> Transformed<'a, I> {
+        Transformed {
+            image,
+            transform: *self,
+        }
+    }
+}
+
+/// An image seen through a [`Transform`].
+pub struct Transformed<'a, I: Image + ?Sized> {
+    image: &'a I,
+    transform: Transform,
+}
+
+impl<'a, I: Image + ?Sized> Image for Transformed<'a, I> {
+    fn size(&self) -> (usize, usize) {
+        let (w, h) = self.image.size();
+        if self.transform.rotation % 2 == 1 {
+            (h, w)
+        } else {
+            (w, h)
+        }
+    }
+
+    fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+        let (w, h) = self.image.size();
+        // Undo the rotation one quarter turn at a time.
+        let (mut x, mut y) = (x, y);
+        for turn in (0..self.transform.rotation).rev() {
+            // Height of the image after `turn` quarter turns.
+            let height = if turn % 2 == 1 { w } else { h };
+            (x, y) = (y, height - 1 - x);
+        }
+        if self.transform.flip {
+            x = w - 1 - x;
+        }
+        self.image.get_pixel_at(x, y)
+    }
+    fn format(&self) -> Result<PixelFormat, String> {
+        self.image.format()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_constraint_fn, ConnectorID, Direction};
+
+    /// 3x2 image where every pixel is unique.
+    struct Numbers;
+
+    impl Image for Numbers {
+        fn size(&self) -> (usize, usize) {
+            (3, 2)
+        }
+        fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4] {
+            [(x + y * 3) as u8, 0, 0, 255]
+        }
+    }
+
+    fn pixels(image: &dyn Image) -> Vec<Vec<u8>> {
+        let (w, h) = image.size();
+        (0..h)
+            .map(|y| (0..w).map(|x| image.get_pixel_at(x, y)[0]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_rotate() {
+        assert_eq!(pixels(&Numbers), vec![vec![0, 1, 2], vec![3, 4, 5]]);
+        assert_eq!(
+            pixels(&Transform::new(1, false).apply(&Numbers)),
+            vec![vec![3, 0], vec![4, 1], vec![5, 2]]
+        );
+        assert_eq!(
+            pixels(&Transform::new(2, false).apply(&Numbers)),
+            vec![vec![5, 4, 3], vec![2, 1, 0]]
+        );
+        assert_eq!(
+            pixels(&Transform::new(3, false).apply(&Numbers)),
+            vec![vec![2, 5], vec![1, 4], vec![0, 3]]
+        );
+    }
+
+    #[test]
+    fn test_flip() {
+        assert_eq!(
+            pixels(&Transform::new(0, true).apply(&Numbers)),
+            vec![vec![2, 1, 0], vec![5, 4, 3]]
+        );
+        assert_eq!(
+            pixels(&Transform::new(1, true).apply(&Numbers)),
+            vec![vec![5, 2], vec![4, 1], vec![3, 0]]
+        );
+    }
+
+    #[test]
+    fn test_connectors() {
+        let constraint_fn = get_constraint_fn(1);
+        let connectors = |image: &dyn Image| -> Vec<ConnectorID> {
+            Direction::all()
+                .into_iter()
+                .map(|d| constraint_fn(image, d))
+                .collect()
+        };
+
+        for rotation in 0..4 {
+            let transform = Transform::new(rotation, false);
+            let image = transform.apply(&Numbers);
+            // Only the order of the sides changes for symmetric sides.
+            assert_eq!(
+                connectors(&image)[rotation as usize],
+                connectors(&Numbers)[0]
+            );
+        }
+    }
+}
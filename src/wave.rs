@@ -1,22 +1,120 @@
-use rand::seq::IteratorRandom;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
-use crate::{conector::ConnectorID, direction::Direction, module::Module, slot::Slot};
+use rand::{seq::IteratorRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    analysis::Analysis,
+    conector::{Collision, ConnectorID, ConnectorRegistry},
+    constraint::{ConnectivityConstraint, CountConstraint, PathConstraint},
+    direction::Direction,
+    dot::{self, DotOptions},
+    edge::{EdgeConstraint, EdgeSampling},
+    module::Module,
+    pixel::PixelFormat,
+    slot::Slot,
+    socket::{side_connector, SocketFn, Sockets},
+};
 
 pub trait Image {
     fn size(&self) -> (usize, usize);
+    /// Returns the color of the pixel at `x`, `y` as 8-bit RGBA, converted from the format of the image.
     fn get_pixel_at(&self, x: usize, y: usize) -> [u8; 4];
+    /// Returns how the pixels of the image are stored. Defaults to 8-bit RGBA.
+    /// Fails if the pixels can't be read, e.g. a compressed texture, `get_pixel_at` then returns
+    /// transparent black.
+    fn format(&self) -> Result<PixelFormat, String> {
+        Ok(PixelFormat::RGBA8)
+    }
 }
 
 pub type ConstraintFn = dyn Fn(&dyn Image, Direction) -> ConnectorID;
-pub type PossibleFn = dyn Fn(Module, Slot, Slot, Direction) -> bool;
+pub type PossibleFn = dyn Fn(Module, Slot, Slot, Direction) -> bool + Send + Sync;
+
+/// How the next slot to collapse is chosen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Heuristic {
+    /// The slot with the fewest possible modules.
+    #[default]
+    Entropy,
+    /// The first slot that is not collapsed, row by row.
+    Scanline,
+    /// Any slot that is not collapsed.
+    Random,
+}
+
+/// How far the collapse of a wave is, returned by [`Wave::run_steps`] and [`Wave::run_for`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of collapsed slots.
+    pub collapsed: usize,
+    /// Number of slots of the grid.
+    pub total: usize,
+    /// Whether every slot is collapsed.
+    pub done: bool,
+}
+
+/// Why a collapse stopped before the wave was solved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollapseError {
+    /// The wave reached a contradiction or broke a constraint.
+    Contradiction(String),
+    /// The cancellation token was set, see [`Wave::set_cancel_token`].
+    Cancelled,
+    /// The deadline passed, see [`Wave::set_deadline`].
+    TimedOut,
+}
+
+impl fmt::Display for CollapseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CollapseError::Contradiction(reason) => write!(f, "{}", reason),
+            CollapseError::Cancelled => write!(f, "Cancelled"),
+            CollapseError::TimedOut => write!(f, "Timed out"),
+        }
+    }
+}
+
+impl std::error::Error for CollapseError {}
+
+impl From<String> for CollapseError {
+    fn from(reason: String) -> Self {
+        CollapseError::Contradiction(reason)
+    }
+}
+
+impl From<CollapseError> for String {
+    fn from(err: CollapseError) -> Self {
+        err.to_string()
+    }
+}
+
 /// Wave holds the state of a wave collapse function.
-pub struct Wave {
+///
+/// Each module carries a payload of type `T`, e.g. the ID of a prefab, set with [`Wave::with_payloads`].
+///
+/// With the `serde` feature the whole state can be saved and restored, except for `is_possible_fn`.
+/// Only whether it was replaced is saved: a wave loaded with a custom function can not collapse
+/// until it is set again, e.g. with [`Wave::set_sockets`] or `Tileset::restore`.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Wave<T = ()> {
     /// Width of the grid.
     width: usize,
     /// Height of the grid.
     height: usize,
     /// Input tiles.
     input: Vec<Module>,
+    /// Payload of each input module.
+    payloads: Vec<T>,
     /// 2D grid of slots.
     pub grid: Vec<Slot>,
 
@@ -24,18 +122,132 @@ pub struct Wave {
     pub history: Vec<Slot>,
 
     /// Override this function to change the behavior of the wave collapse function.
-    pub is_possible_fn: Box<PossibleFn>,
+    /// It is not saved with the `serde` feature and has to be set again after loading.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "custom_possible_fn",
+            default = "default_possible_fn",
+            serialize_with = "serialize_possible_fn",
+            deserialize_with = "deserialize_possible_fn"
+        )
+    )]
+    pub is_possible_fn: Arc<PossibleFn>,
+
+    /// Random number generator used to collapse the slots.
+    rng: ChaCha8Rng,
+    /// How the next slot to collapse is chosen.
+    #[cfg_attr(feature = "serde", serde(default))]
+    heuristic: Heuristic,
+    /// Whether the edges of the grid wrap around.
+    #[cfg_attr(feature = "serde", serde(default))]
+    periodic: bool,
+
+    /// Limits on how many slots can hold each module.
+    count_constraints: Vec<CountConstraint>,
+    /// Groups of modules that must form a single region.
+    connectivity_constraints: Vec<ConnectivityConstraint>,
+    /// Routes that must exist between two slots.
+    path_constraints: Vec<PathConstraint>,
+    /// Number of times the wave started over since `initialize`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    restarts: usize,
+
+    /// Sources of the connectors, used to name them.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    registry: Option<ConnectorRegistry>,
+
+    /// Stops the collapse when set.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cancel: Option<Arc<AtomicBool>>,
+    /// Stops the collapse when passed.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    deadline: Option<Instant>,
+}
+
+/// Modules are possible if their connectors match the ones of the slot they come from.
+/// Always the same `Arc`, to tell it apart from custom functions.
+fn default_possible_fn() -> Arc<PossibleFn> {
+    static DEFAULT: OnceLock<Arc<PossibleFn>> = OnceLock::new();
+    DEFAULT
+        .get_or_init(|| Arc::new(|module, from, _to, d| module.is_possible(&from, d)))
+        .clone()
 }
 
-impl Default for Wave {
+/// Stands for a custom function that was not saved, the wave refuses to collapse until it is replaced.
+fn missing_possible_fn() -> Arc<PossibleFn> {
+    static MISSING: OnceLock<Arc<PossibleFn>> = OnceLock::new();
+    MISSING
+        .get_or_init(|| Arc::new(|_module, _from, _to, _d| false))
+        .clone()
+}
+
+/// Saves whether `is_possible_fn` was replaced.
+#[cfg(feature = "serde")]
+fn serialize_possible_fn<S: serde::Serializer>(
+    is_possible_fn: &Arc<PossibleFn>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bool(!Arc::ptr_eq(is_possible_fn, &default_possible_fn()))
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_possible_fn<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Arc<PossibleFn>, D::Error> {
+    let custom = <bool as serde::Deserialize>::deserialize(deserializer)?;
+    Ok(if custom {
+        missing_possible_fn()
+    } else {
+        default_possible_fn()
+    })
+}
+
+impl<T> Default for Wave<T> {
     fn default() -> Self {
         Self {
             width: 0,
             height: 0,
             input: Vec::new(),
+            payloads: Vec::new(),
             grid: Vec::new(),
             history: Vec::new(),
-            is_possible_fn: Box::new(|module, from, _to, d| module.is_possible(&from, d)),
+            is_possible_fn: default_possible_fn(),
+            rng: ChaCha8Rng::from_entropy(),
+            heuristic: Heuristic::default(),
+            periodic: false,
+            count_constraints: Vec::new(),
+            connectivity_constraints: Vec::new(),
+            path_constraints: Vec::new(),
+            restarts: 0,
+            registry: None,
+            cancel: None,
+            deadline: None,
+        }
+    }
+}
+
+/// Clones share `is_possible_fn`, the connector registry and the cancellation token.
+impl<T: Clone> Clone for Wave<T> {
+    fn clone(&self) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            input: self.input.clone(),
+            payloads: self.payloads.clone(),
+            grid: self.grid.clone(),
+            history: self.history.clone(),
+            is_possible_fn: self.is_possible_fn.clone(),
+            rng: self.rng.clone(),
+            heuristic: self.heuristic,
+            periodic: self.periodic,
+            count_constraints: self.count_constraints.clone(),
+            connectivity_constraints: self.connectivity_constraints.clone(),
+            path_constraints: self.path_constraints.clone(),
+            restarts: self.restarts,
+            registry: self.registry.clone(),
+            cancel: self.cancel.clone(),
+            deadline: self.deadline,
         }
     }
 }
@@ -44,10 +256,32 @@ impl Wave {
     /// Create a new wave collapse function with the given width and height.
     /// The default constraint function will check 3 pixels in each direction.
     /// Use `with_custom_constraint` to override the default behavior of the wave collapse function.
+    ///
+    /// The connectors are recorded in a new registry, which names them and finds the different edges
+    /// that share one, see [`Wave::collisions`]. Use `with_registry` to fail on collisions instead.
+    /// Images whose pixels can't be read are sampled as transparent black, use `try_new` to reject them.
     pub fn new(input: &[impl Image], width: usize, height: usize) -> Self {
-        Wave::with_custom_constraint(input, width, height, get_constraint_fn(3))
+        let registry = ConnectorRegistry::new();
+        let mut wave =
+            Wave::with_custom_constraint(input, width, height, registry.constraint_fn(3));
+        wave.registry = Some(registry);
+        wave
     }
 
+    /// Create a new wave like `new`, failing if the pixels of an image can't be read.
+    pub fn try_new(input: &[impl Image], width: usize, height: usize) -> Result<Self, String> {
+        for (idx, image) in input.iter().enumerate() {
+            image
+                .format()
+                .map_err(|err| format!("Image {}: {}", idx, err))?;
+        }
+        Ok(Wave::new(input, width, height))
+    }
+
+    /// Create a new wave whose connectors are computed by `custom_contraint_fn`.
+    ///
+    /// The connectors are not recorded. To name them and find collisions, build the function with
+    /// [`EdgeConstraint::build_registered`] and pass the same registry to `set_registry`.
     pub fn with_custom_constraint(
         input: &[impl Image],
         width: usize,
@@ -66,17 +300,286 @@ impl Wave {
             modules.push(module);
         }
 
+        Wave::from_modules(modules, width, height)
+    }
+
+    /// Create a new wave with the default constraint function, recording the connectors in `registry`.
+    /// Fails if two different edges have the same connector.
+    pub fn with_registry(
+        input: &[impl Image],
+        width: usize,
+        height: usize,
+        registry: ConnectorRegistry,
+    ) -> Result<Self, String> {
+        let mut wave =
+            Wave::with_custom_constraint(input, width, height, registry.constraint_fn(3));
+        if let Some(collision) = registry.collisions().first() {
+            return Err(format!(
+                "Connector {} is shared by {}",
+                collision.id,
+                collision.sources.join(" and ")
+            ));
+        }
+        wave.registry = Some(registry);
+        Ok(wave)
+    }
+
+    /// Create a new wave whose sides are made of several sockets, see [`socket_fn`](crate::socket_fn).
+    /// Modules fit when their sockets are equal one by one.
+    pub fn with_sockets(
+        input: &[impl Image],
+        width: usize,
+        height: usize,
+        socket_fn: Box<SocketFn>,
+    ) -> Self {
+        let sides = input
+            .iter()
+            .map(|image| {
+                let sides: Vec<_> = Direction::all()
+                    .into_iter()
+                    .map(|direction| socket_fn(image, direction))
+                    .collect();
+                sides.try_into().expect("Four directions")
+            })
+            .collect();
+        let mut wave =
+            Wave::from_modules((0..input.len()).map(Module::new).collect(), width, height);
+        wave.set_sockets(Sockets::new(sides));
+        wave
+    }
+
+    /// Create a new wave collapse function from already built modules.
+    pub(crate) fn from_modules(modules: Vec<Module>, width: usize, height: usize) -> Self {
         Self {
             width,
             height,
+            payloads: vec![(); modules.len()],
             input: modules,
             ..Default::default()
         }
     }
+}
+
+impl<T> Wave<T> {
+    /// Attach a payload to each module, in the order of the input images.
+    /// Fails if there is not one payload per module.
+    /// ```
+    /// use billow::Wave;
+    /// # struct Tile;
+    /// # impl billow::Image for Tile {
+    /// #     fn size(&self) -> (usize, usize) { (1, 1) }
+    /// #     fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] { [0; 4] }
+    /// # }
+    ///
+    /// let mut wave = Wave::new(&[Tile, Tile], 2, 2)
+    ///     .with_payloads(vec!["grass", "water"])
+    ///     .unwrap();
+    /// wave.initialize();
+    /// wave.collapse(100).unwrap();
+    /// assert!(["grass", "water"].contains(wave.payload_at(0, 0).unwrap()));
+    /// ```
+    pub fn with_payloads<U>(self, payloads: Vec<U>) -> Result<Wave<U>, String> {
+        if payloads.len() != self.input.len() {
+            return Err(format!(
+                "Expected {} payloads, got {}",
+                self.input.len(),
+                payloads.len()
+            ));
+        }
+        Ok(Wave {
+            width: self.width,
+            height: self.height,
+            input: self.input,
+            payloads,
+            grid: self.grid,
+            history: self.history,
+            is_possible_fn: self.is_possible_fn,
+            rng: self.rng,
+            heuristic: self.heuristic,
+            periodic: self.periodic,
+            count_constraints: self.count_constraints,
+            connectivity_constraints: self.connectivity_constraints,
+            path_constraints: self.path_constraints,
+            restarts: self.restarts,
+            registry: self.registry,
+            cancel: self.cancel,
+            deadline: self.deadline,
+        })
+    }
+
+    /// Returns the payload of the slot at `x`, `y`, if it is collapsed.
+    pub fn payload_at(&self, x: usize, y: usize) -> Option<&T> {
+        if x >= self.width {
+            return None;
+        }
+        match self.grid.get(x + y * self.width)?.superposition.as_slice() {
+            [module] => self.payloads.get(module.index),
+            _ => None,
+        }
+    }
+
+    /// Returns the payload of every slot row by row, `None` for the slots that are not collapsed.
+    pub fn payloads(&self) -> Vec<Option<&T>> {
+        self.grid
+            .iter()
+            .map(|slot| self.payload_at(slot.x, slot.y))
+            .collect()
+    }
+
+    /// Compare the sockets of the modules to decide which ones fit together,
+    /// replacing `is_possible_fn` and the connectors of the modules.
+    /// Call it before `initialize`.
+    pub fn set_sockets(&mut self, sockets: Sockets) {
+        for module in self.input.iter_mut() {
+            if let Some(sides) = sockets.sides.get(module.index) {
+                for direction in Direction::all() {
+                    module.connectors[direction as usize] =
+                        side_connector(&sides[direction as usize], direction);
+                }
+            }
+        }
+        self.is_possible_fn = sockets.is_possible_fn();
+    }
+
+    /// Set the registry used to name the connectors.
+    pub fn set_registry(&mut self, registry: ConnectorRegistry) {
+        self.registry = Some(registry);
+    }
+
+    /// Returns the label of a connector if it is in the registry of the wave,
+    /// or its hexadecimal ID otherwise.
+    pub fn connector_name(&self, id: ConnectorID) -> String {
+        match &self.registry {
+            Some(registry) => registry.name(id),
+            None => id.to_string(),
+        }
+    }
+
+    /// Returns the connectors shared by different edges, found by the registry of the wave.
+    /// Always empty without a registry.
+    pub fn collisions(&self) -> Vec<Collision> {
+        match &self.registry {
+            Some(registry) => registry.collisions(),
+            None => vec![],
+        }
+    }
+
+    /// Width of the grid.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the grid.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Seed the random number generator, so the same seed always generates the same grid.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    /// Choose how the next slot to collapse is picked. Defaults to [`Heuristic::Entropy`].
+    pub fn set_heuristic(&mut self, heuristic: Heuristic) {
+        self.heuristic = heuristic;
+    }
+
+    /// Make the edges of the grid wrap around, so the output can be tiled.
+    pub fn set_periodic(&mut self, periodic: bool) {
+        self.periodic = periodic;
+    }
+
+    /// Stop the collapse once `token` is set, e.g. from another thread.
+    /// The token is checked between iterations, so the wave is left in a consistent state.
+    ///
+    /// Clones of the wave share the token: setting it cancels all of them, including every attempt
+    /// of `collapse_parallel`. Give a clone its own token to stop it alone.
+    /// ```
+    /// use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+    /// use billow::{CollapseError, Wave};
+    /// # struct Tile;
+    /// # impl billow::Image for Tile {
+    /// #     fn size(&self) -> (usize, usize) { (1, 1) }
+    /// #     fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] { [0; 4] }
+    /// # }
+    ///
+    /// let token = Arc::new(AtomicBool::new(false));
+    /// let mut wave = Wave::new(&[Tile, Tile], 20, 20);
+    /// wave.set_cancel_token(token.clone());
+    /// wave.initialize();
+    ///
+    /// token.store(true, Ordering::Relaxed);
+    /// assert_eq!(wave.collapse(1000), Err(CollapseError::Cancelled));
+    /// ```
+    pub fn set_cancel_token(&mut self, token: Arc<AtomicBool>) {
+        self.cancel = Some(token);
+    }
+
+    /// Stop the collapse once `deadline` is passed, `None` to run without a deadline.
+    /// The deadline is checked between iterations, so the wave is left in a consistent state.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Look for connectors and modules that make the wave fail, using `is_possible_fn`
+    /// to decide which modules fit together.
+    pub fn analyze(&self) -> Analysis {
+        Analysis::new(&self.input, self.is_possible_fn.as_ref(), &|id| {
+            self.connector_name(id)
+        })
+    }
+
+    /// Returns the adjacency graph of the modules as a Graphviz DOT string.
+    /// Uses `is_possible_fn` to decide which modules fit together.
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        dot::graph(
+            &self.input,
+            self.is_possible_fn.as_ref(),
+            &|id| self.connector_name(id),
+            options,
+        )
+    }
+
+    /// Add a constraint on the number of slots that can hold some modules.
+    /// The constraint is enforced after each iteration of `collapse`.
+    pub fn add_count_constraint(&mut self, constraint: CountConstraint) {
+        self.count_constraints.push(constraint);
+    }
+
+    /// Add a constraint that keeps a group of modules connected.
+    /// Unless the constraint is `check_only`, the wave starts over as soon as the group can no longer be
+    /// connected, and `collapse` fails once the constraint is out of restarts.
+    pub fn add_connectivity_constraint(&mut self, constraint: ConnectivityConstraint) {
+        self.connectivity_constraints.push(constraint);
+    }
+
+    /// Add a constraint that requires a route between two slots.
+    /// `collapse` fails as soon as the route can no longer exist or be short enough.
+    /// The minimum length is verified once the wave is collapsed.
+    pub fn add_path_constraint(&mut self, constraint: PathConstraint) {
+        self.path_constraints.push(constraint);
+    }
 
     /// Populate the grid with the input modules.
     /// Choose a random starting point.
     pub fn initialize(&mut self) {
+        self.restarts = 0;
+        self.start();
+    }
+
+    /// Reset the grid and collapse a random slot.
+    fn start(&mut self) {
+        self.reset_grid();
+        self.history.clear();
+
+        // TODO: Move this to a separate function. (initialize_random)
+        if let Some(slot) = self.collapse_random() {
+            self.history.push(slot);
+        }
+    }
+
+    /// Fill the grid with slots that can hold any input module.
+    pub(crate) fn reset_grid(&mut self) {
         self.grid = vec![Slot::default(); self.width * self.height];
         self.grid.iter_mut().enumerate().for_each(|(idx, slot)| {
             let x = idx % self.width;
@@ -85,40 +588,86 @@ impl Wave {
             slot.y = y;
             slot.superposition = self.input.clone();
         });
-
-        // TODO: Move this to a separate function. (initialize_random)
-        if let Some(slot) = self.collapse_random() {
-            self.history.push(slot);
-        }
     }
 
     /// Pick a random slot from the grid and collapse it.
     pub fn collapse_random(&mut self) -> Option<Slot> {
-        if let Some(slot) = self.grid.iter_mut().choose(&mut rand::thread_rng()) {
-            slot.collapse();
+        if let Some(slot) = self.grid.iter_mut().choose(&mut self.rng) {
+            slot.collapse(&mut self.rng);
             return Some(slot.clone());
         }
         None
     }
 
     /// Pick the slot with the lowest superposition size.
+    /// Returns `None` if no slot has more than one possible module.
     pub fn collapse_least_entropy(&mut self) -> Option<Slot> {
-        let mut least_index = 0;
-        let mut least_entropy = self.input.len();
+        let mut least_index = None;
+        // Not the number of modules, or slots that can still hold every module are never picked.
+        let mut least_entropy = usize::MAX;
         // TODO: Use reduce?
         self.grid.iter().enumerate().for_each(|(idx, slot)| {
             let entropy = slot.superposition.len();
             if entropy > 1 && entropy < least_entropy {
-                least_index = idx;
+                least_index = Some(idx);
                 least_entropy = entropy;
             }
         });
 
-        if let Some(slot) = self.grid.get_mut(least_index) {
-            slot.collapse();
-            return Some(slot.clone());
+        let slot = &mut self.grid[least_index?];
+        slot.collapse(&mut self.rng);
+        Some(slot.clone())
+    }
+
+    /// Pick the next slot with the heuristic of the wave and collapse it.
+    pub(crate) fn observe(&mut self) -> Option<Slot> {
+        let uncollapsed = |slot: &&mut Slot| slot.superposition.len() > 1;
+        let slot = match self.heuristic {
+            Heuristic::Entropy => return self.collapse_least_entropy(),
+            Heuristic::Scanline => self.grid.iter_mut().find(uncollapsed),
+            Heuristic::Random => self
+                .grid
+                .iter_mut()
+                .filter(uncollapsed)
+                .choose(&mut self.rng),
+        }?;
+        slot.collapse(&mut self.rng);
+        Some(slot.clone())
+    }
+
+    /// Returns true if the rules of the wave only involve neighbor slots,
+    /// so that areas of the grid can be solved on their own.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn is_local(&self) -> bool {
+        !self.periodic
+            && self.count_constraints.is_empty()
+            && self.connectivity_constraints.is_empty()
+            && self.path_constraints.is_empty()
+    }
+
+    /// Returns a wave over an area of the grid, with the same modules and `is_possible_fn`.
+    /// The slots of the area are copied with coordinates relative to its top left corner.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn area(&self, x: usize, y: usize, width: usize, height: usize) -> Wave {
+        let mut grid = Vec::with_capacity(width * height);
+        for row in y..y + height {
+            for column in x..x + width {
+                let mut slot = self.grid[row * self.width + column].clone();
+                slot.x = column - x;
+                slot.y = row - y;
+                grid.push(slot);
+            }
+        }
+        Wave {
+            width,
+            height,
+            input: self.input.clone(),
+            payloads: vec![(); self.input.len()],
+            grid,
+            is_possible_fn: self.is_possible_fn.clone(),
+            heuristic: self.heuristic,
+            ..Default::default()
         }
-        None
     }
 
     /// Check if all the slots have been collapsed.
@@ -126,8 +675,122 @@ impl Wave {
         self.grid.iter().all(|slot| slot.superposition.len() == 1)
     }
 
+    /// Checks if the slots holding a module of the group form a single connected region.
+    /// Neighbor slots are connected when both can hold a module of the group with matching connectors.
+    ///
+    /// The result is exact once the wave is collapsed. Before that, it is false only if
+    /// the group can no longer be connected.
+    pub fn is_connected(&self, modules: &[usize]) -> bool {
+        let in_group = |module: &&Module| modules.contains(&module.index);
+        let holds = |slot: &Slot| {
+            !slot.superposition.is_empty() && slot.superposition.iter().all(|m| in_group(&m))
+        };
+
+        let start = match self.grid.iter().position(holds) {
+            Some(idx) => idx,
+            None => return true,
+        };
+
+        let mut visited = vec![false; self.grid.len()];
+        visited[start] = true;
+        let mut pending = vec![start];
+        while let Some(idx) = pending.pop() {
+            let slot = &self.grid[idx];
+            for direction in Direction::all() {
+                if !self.has_neighbor(slot, direction) {
+                    continue;
+                }
+
+                let next_slot = self.get_neighbor(slot, direction).expect("No next slot");
+                let next_idx = next_slot.y * self.width + next_slot.x;
+                if visited[next_idx] {
+                    continue;
+                }
+
+                let back = direction.reverse();
+                let linked =
+                    slot.superposition.iter().filter(in_group).any(|a| {
+                        next_slot.superposition.iter().filter(in_group).any(|b| {
+                            a.connectors[direction as usize] == b.connectors[back as usize]
+                        })
+                    });
+                if linked {
+                    visited[next_idx] = true;
+                    pending.push(next_idx);
+                }
+            }
+        }
+
+        self.grid
+            .iter()
+            .enumerate()
+            .all(|(idx, slot)| visited[idx] || !holds(slot))
+    }
+
+    /// Returns the index in the grid of the slot at `x`, `y`, if it is inside the grid.
+    fn slot_index(&self, (x, y): (usize, usize)) -> Option<usize> {
+        (x < self.width && y < self.height).then(|| x + y * self.width)
+    }
+
+    /// Returns the number of steps of the shortest route between two slots.
+    /// Neighbor slots are linked when they can hold modules with matching connectors
+    /// and the connector is one of the given passable `connectors`.
+    ///
+    /// The result is exact once the wave is collapsed. Before that, it is the shortest route
+    /// that is still possible. There is no route if a slot is outside the grid.
+    pub fn path_length(
+        &self,
+        start: (usize, usize),
+        goal: (usize, usize),
+        connectors: &[ConnectorID],
+    ) -> Option<usize> {
+        let start_idx = self.slot_index(start)?;
+        let goal_idx = self.slot_index(goal)?;
+
+        let mut distance = vec![None; self.grid.len()];
+        distance[start_idx] = Some(0);
+        let mut pending = std::collections::VecDeque::from([start_idx]);
+        while let Some(idx) = pending.pop_front() {
+            if idx == goal_idx {
+                return distance[idx];
+            }
+
+            let slot = &self.grid[idx];
+            for direction in Direction::all() {
+                if !self.has_neighbor(slot, direction) {
+                    continue;
+                }
+
+                let next_slot = self.get_neighbor(slot, direction).expect("No next slot");
+                let next_idx = next_slot.y * self.width + next_slot.x;
+                if distance[next_idx].is_some() {
+                    continue;
+                }
+
+                let back = direction.reverse();
+                let linked = slot.superposition.iter().any(|a| {
+                    let connector = a.connectors[direction as usize];
+                    connectors.contains(&connector)
+                        && next_slot
+                            .superposition
+                            .iter()
+                            .any(|b| b.connectors[back as usize] == connector)
+                });
+                if linked {
+                    distance[next_idx] = distance[idx].map(|d| d + 1);
+                    pending.push_back(next_idx);
+                }
+            }
+        }
+        None
+    }
+
     /// Checks if the slot has a neighbor in the given direction. (To avoid out of bounds errors.)
+    /// Every slot has all its neighbors when the wave is periodic.
     fn has_neighbor(&self, slot: &Slot, direction: Direction) -> bool {
+        if self.periodic {
+            return true;
+        }
         match direction {
             Direction::Up => slot.y > 0,
             Direction::Down => slot.y < self.height - 1,
@@ -136,20 +799,15 @@ impl Wave {
         }
     }
 
-    /// Get the neighbor of the slot in the given direction.
+    /// Get the neighbor of the slot in the given direction, wrapping around the edges.
     fn get_neighbor(&self, slot: &Slot, direction: Direction) -> Option<&Slot> {
-        match direction {
-            Direction::Up => self.grid.get(slot.x + (slot.y - 1) * self.width),
-            Direction::Down => self.grid.get(slot.x + (slot.y + 1) * self.width),
-            Direction::Left => self.grid.get((slot.x - 1) + slot.y * self.width),
-            Direction::Right => self.grid.get((slot.x + 1) + slot.y * self.width),
-        }
-    }
-
-    /// Checks if the slot has already been visited in the current iteration.
-    /// This is used to avoid propagating the same slot multiple times.
-    fn has_visited(&self, slot: &Slot) -> bool {
-        self.history.iter().any(|s| s.x == slot.x && s.y == slot.y)
+        let (x, y) = match direction {
+            Direction::Up => (slot.x, (slot.y + self.height - 1) % self.height),
+            Direction::Down => (slot.x, (slot.y + 1) % self.height),
+            Direction::Left => ((slot.x + self.width - 1) % self.width, slot.y),
+            Direction::Right => ((slot.x + 1) % self.width, slot.y),
+        };
+        self.grid.get(x + y * self.width)
     }
 
     /// Returns the possible modules for the given slot.
@@ -163,68 +821,288 @@ impl Wave {
         possible_modules
     }
 
-    /// Collapse one slot and propagate the collapse to all the neighbors.
+    /// Collapse one slot, or take the slots collapsed since the last iteration,
+    /// and propagate the change until no more modules can be removed.
     fn recurse(&mut self) -> Result<(), String> {
         if self.is_collapsed() {
             return Ok(());
         }
 
         if self.history.is_empty() {
-            if let Some(slot) = self.collapse_least_entropy() {
-                self.history.push(slot);
-            } else {
-                return Ok(());
+            // Only slots without any possible module are left.
+            let Some(slot) = self.observe() else {
+                let slot = self
+                    .grid
+                    .iter()
+                    .find(|slot| slot.superposition.is_empty())
+                    .expect("An empty slot");
+                return Err(format!(
+                    "No possible modules for slot ({}, {})",
+                    slot.x, slot.y
+                ));
+            };
+            self.history.push(slot);
+        }
+
+        let changed = self
+            .history
+            .iter()
+            .map(|slot| slot.y * self.width + slot.x)
+            .collect();
+        self.propagate(changed)
+    }
+
+    /// Propagate the changes of the given slots until no more modules can be removed.
+    pub(crate) fn propagate(&mut self, mut pending: Vec<usize>) -> Result<(), String> {
+        while let Some(idx) = pending.pop() {
+            let slot = self.grid[idx].clone();
+            for direction in Direction::all() {
+                if !self.has_neighbor(&slot, direction) {
+                    continue;
+                }
+
+                let next_slot = self.get_neighbor(&slot, direction).expect("No next slot");
+                let possible_modules = self.get_possible_modules(&slot, next_slot, direction);
+                if possible_modules.len() == next_slot.superposition.len() {
+                    continue;
+                }
+
+                let next_idx = next_slot.y * self.width + next_slot.x;
+                if possible_modules.is_empty() {
+                    return Err(format!(
+                        "No possible modules for slot ({}, {})",
+                        next_slot.x, next_slot.y
+                    ));
+                }
+                self.grid[next_idx].superposition = possible_modules;
+                pending.push(next_idx);
             }
         }
+        Ok(())
+    }
+
+    /// Ban or force the modules of the count constraints until all of them can still be met.
+    fn enforce_count_constraints(&mut self) -> Result<(), String> {
+        let total = self.width * self.height;
+
+        loop {
+            let mut changed = vec![];
+
+            for constraint in &self.count_constraints {
+                let (min, max) = (constraint.min_slots(total), constraint.max_slots(total));
+
+                // Slots that can only hold a module of the group, and slots that could hold one.
+                let placed = self.grid.iter().filter(|s| constraint.holds(s)).count();
+                let possible = self.grid.iter().filter(|s| constraint.fits(s)).count();
 
-        let previous_slot = self.history.last().expect("No previous slot").clone();
-        for direction in Direction::all() {
-            if !self.has_neighbor(&previous_slot, direction) {
-                continue;
+                if placed > max {
+                    return Err(format!(
+                        "Modules {:?} placed in {} slots, at most {} allowed",
+                        constraint.modules, placed, max
+                    ));
+                }
+                if possible < min {
+                    return Err(format!(
+                        "Modules {:?} fit in {} slots, at least {} required",
+                        constraint.modules, possible, min
+                    ));
+                }
+
+                if placed == max && possible > placed {
+                    // Maximum reached, ban the group everywhere else.
+                    for (idx, slot) in self.grid.iter_mut().enumerate() {
+                        if !constraint.holds(slot) && slot.retain(|m| !constraint.contains(m.index))
+                        {
+                            changed.push(idx);
+                        }
+                    }
+                } else if possible == min && placed < possible {
+                    // Every slot that can hold the group is needed to reach the minimum.
+                    for (idx, slot) in self.grid.iter_mut().enumerate() {
+                        if constraint.fits(slot) && slot.retain(|m| constraint.contains(m.index)) {
+                            changed.push(idx);
+                        }
+                    }
+                }
+
+                if !changed.is_empty() {
+                    break;
+                }
             }
 
-            let next_slot = &mut self
-                .get_neighbor(&previous_slot, direction)
-                .expect("No next slot")
-                .to_owned();
-            if self.has_visited(next_slot) {
-                continue;
+            if changed.is_empty() {
+                return Ok(());
             }
+            self.propagate(changed)?;
+        }
+    }
 
-            let possible_modules = self.get_possible_modules(&previous_slot, next_slot, direction);
-            if possible_modules.len() == next_slot.superposition.len() {
-                continue;
-            } else {
-                next_slot.superposition = possible_modules;
-                // TODO: This is a bit of a hack. Should be able to do this mutably.
-                self.grid[next_slot.y * self.width + next_slot.x] = next_slot.clone();
+    /// Start over if a group of modules can no longer be connected, and fail once the constraint
+    /// is out of restarts. `check_only` constraints are verified once the wave is collapsed.
+    fn check_connectivity_constraints(&mut self) -> Result<(), String> {
+        let collapsed = self.is_collapsed();
+        let broken = self.connectivity_constraints.iter().find(|constraint| {
+            (collapsed || !constraint.check_only) && !self.is_connected(&constraint.modules)
+        });
+        match broken {
+            None => Ok(()),
+            Some(constraint) if constraint.check_only || self.restarts >= constraint.restarts => {
+                Err(format!(
+                    "Modules {:?} are not connected after {} restarts",
+                    constraint.modules, self.restarts
+                ))
             }
+            Some(_) => {
+                self.restarts += 1;
+                self.start();
+                Ok(())
+            }
+        }
+    }
 
-            if next_slot.superposition.is_empty() {
+    /// Fail if a route can no longer exist or have a valid length.
+    fn check_path_constraints(&self) -> Result<(), String> {
+        let collapsed = self.is_collapsed();
+        for constraint in &self.path_constraints {
+            let (start, goal) = (constraint.start, constraint.goal);
+            if let Some(outside) = [start, goal]
+                .into_iter()
+                .find(|s| self.slot_index(*s).is_none())
+            {
                 return Err(format!(
-                    "No possible modules for slot ({}, {})",
-                    next_slot.x, next_slot.y
+                    "Slot {:?} of the path is outside the {}x{} grid",
+                    outside, self.width, self.height
                 ));
             }
+            let length = match self.path_length(start, goal, &constraint.connectors) {
+                Some(length) => length,
+                None => return Err(format!("No path from {:?} to {:?}", start, goal)),
+            };
 
-            self.history.push(next_slot.clone());
-            self.recurse()?;
-            self.history.pop();
+            if let Some(max) = constraint.max_length.filter(|max| length > *max) {
+                return Err(format!(
+                    "Path from {:?} to {:?} is longer than {} steps",
+                    start, goal, max
+                ));
+            }
+            if let Some(min) = constraint
+                .min_length
+                .filter(|min| collapsed && length < *min)
+            {
+                return Err(format!(
+                    "Path from {:?} to {:?} is shorter than {} steps",
+                    start, goal, min
+                ));
+            }
         }
         Ok(())
     }
 
     /// Run `attemps` iterations of the algorithm.
-    pub fn collapse(&mut self, attemps: i32) -> Result<(), String> {
+    pub fn collapse(&mut self, attemps: i32) -> Result<(), CollapseError> {
+        self.collapse_with(attemps, |_| {})
+    }
+
+    /// Run `attemps` iterations of the algorithm, calling `observer` after each one.
+    /// Every iteration collapses one slot and propagates the change.
+    pub fn collapse_with(
+        &mut self,
+        attemps: i32,
+        mut observer: impl FnMut(&Wave<T>),
+    ) -> Result<(), CollapseError> {
         for _ in 0..attemps {
-            self.recurse()?;
-            self.history.clear();
+            self.step()?;
+            observer(self);
             if self.is_collapsed() {
                 return Ok(());
             }
         }
         Ok(())
     }
+
+    /// Run at most `steps` iterations of the algorithm and returns how far the collapse is.
+    /// The state is kept between calls, so the collapse can be spread over several frames of a game.
+    /// ```
+    /// use billow::Wave;
+    /// # struct Tile;
+    /// # impl billow::Image for Tile {
+    /// #     fn size(&self) -> (usize, usize) { (1, 1) }
+    /// #     fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] { [0; 4] }
+    /// # }
+    ///
+    /// let mut wave = Wave::new(&[Tile, Tile], 20, 20);
+    /// wave.initialize();
+    /// while !wave.run_steps(10).unwrap().done {
+    ///     // Render a frame.
+    /// }
+    /// ```
+    pub fn run_steps(&mut self, steps: usize) -> Result<Progress, CollapseError> {
+        for _ in 0..steps {
+            if self.is_collapsed() {
+                break;
+            }
+            self.step()?;
+        }
+        Ok(self.progress())
+    }
+
+    /// Run iterations of the algorithm until `budget` is spent and returns how far the collapse is.
+    /// At least one iteration runs, so the collapse always moves forward.
+    pub fn run_for(&mut self, budget: Duration) -> Result<Progress, CollapseError> {
+        let start = Instant::now();
+        while !self.is_collapsed() {
+            self.step()?;
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+        Ok(self.progress())
+    }
+
+    /// Returns how far the collapse is.
+    pub fn progress(&self) -> Progress {
+        let collapsed = self
+            .grid
+            .iter()
+            .filter(|slot| slot.superposition.len() == 1)
+            .count();
+        Progress {
+            collapsed,
+            total: self.grid.len(),
+            done: collapsed == self.grid.len(),
+        }
+    }
+
+    /// Collapse one slot, propagate the change and check the constraints.
+    /// Stops before collapsing if the wave is cancelled or its deadline is passed.
+    /// Fails if the wave was loaded with a custom `is_possible_fn` that was not set again.
+    pub(crate) fn check_possible_fn(&self) -> Result<(), CollapseError> {
+        if Arc::ptr_eq(&self.is_possible_fn, &missing_possible_fn()) {
+            return Err(CollapseError::Contradiction(String::from(
+                "The wave was saved with a custom is_possible_fn, set it again after loading",
+            )));
+        }
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<(), CollapseError> {
+        self.check_possible_fn()?;
+        if let Some(cancel) = &self.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(CollapseError::Cancelled);
+            }
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(CollapseError::TimedOut);
+            }
+        }
+        self.recurse()?;
+        self.history.clear();
+        self.enforce_count_constraints()?;
+        self.check_connectivity_constraints()?;
+        Ok(self.check_path_constraints()?)
+    }
 }
 
 /// Returns the default constraint function.
@@ -233,27 +1111,258 @@ impl Wave {
 /// The default constraint function checks pixel equality.
 /// Use the `sample_size` to control the number of pixels to compare on each side of the slot.
 pub fn get_constraint_fn(sample_size: usize) -> Box<ConstraintFn> {
-    let count = sample_size + 1; // +1 for the center pixel
+    EdgeConstraint::new()
+        .sampling(EdgeSampling::Points(sample_size))
+        .build()
+}
 
-    Box::new(move |img: &dyn Image, dir: Direction| {
-        let (w, h) = img.size();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{is_valid, run, solve, Edges};
 
-        let dx = w / count;
-        let dy = h / count;
+    fn tiles() -> Vec<Edges> {
+        vec![
+            Edges([0, 0, 0, 0]),
+            Edges([0, 1, 0, 1]),
+            Edges([1, 0, 1, 0]),
+            Edges([0, 1, 1, 0]),
+            Edges([1, 0, 0, 1]),
+            Edges([1, 1, 1, 1]),
+        ]
+    }
 
-        let mut id = vec![];
+    fn indices(wave: &Wave) -> Vec<Vec<usize>> {
+        wave.grid
+            .iter()
+            .map(|slot| slot.superposition.iter().map(|m| m.index).collect())
+            .collect()
+    }
 
-        for idx in 0..count - 1 {
-            let pixel = match dir {
-                Direction::Up => img.get_pixel_at(dx + idx * dx, 0),
-                Direction::Right => img.get_pixel_at(w as usize - 1, dy + idx * dy),
-                Direction::Down => img.get_pixel_at(dx + idx * dx, h as usize - 1),
-                Direction::Left => img.get_pixel_at(0, dy + idx * dy),
-            };
+    #[test]
+    fn test_run_steps() {
+        let mut a = Wave::new(&tiles(), 10, 10);
+        let mut b = Wave::new(&tiles(), 10, 10);
+        a.set_seed(42);
+        b.set_seed(42);
+        a.initialize();
+        b.initialize();
 
-            id.push(pixel);
+        let first = b.run_steps(5).unwrap();
+        assert_eq!(first.total, 100);
+        assert!(first.collapsed >= 5 && !first.done);
+
+        let mut steps = 1;
+        while !b.run_steps(5).unwrap().done {
+            steps += 1;
         }
+        assert!(steps > 1);
+        assert_eq!(b.run_steps(5).unwrap(), b.progress());
 
-        ConnectorID::from(id.concat())
-    })
+        run(&mut a).unwrap();
+        assert_eq!(indices(&a), indices(&b));
+        assert!(is_valid(&b));
+
+        let mut c = Wave::new(&tiles(), 10, 10);
+        c.set_seed(42);
+        c.initialize();
+        while !c.run_for(Duration::ZERO).unwrap().done {}
+        assert!(c.is_collapsed());
+        assert!(is_valid(&c));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let token = Arc::new(AtomicBool::new(false));
+        let mut wave = Wave::new(&tiles(), 10, 10);
+        wave.set_seed(42);
+        wave.set_cancel_token(token.clone());
+        wave.initialize();
+        wave.run_steps(5).unwrap();
+
+        token.store(true, Ordering::Relaxed);
+        let before = indices(&wave);
+        assert_eq!(wave.collapse(100), Err(CollapseError::Cancelled));
+        assert_eq!(wave.run_steps(5), Err(CollapseError::Cancelled));
+        assert_eq!(indices(&wave), before);
+
+        token.store(false, Ordering::Relaxed);
+        wave.set_deadline(Some(Instant::now()));
+        assert_eq!(wave.collapse(100), Err(CollapseError::TimedOut));
+        assert_eq!(indices(&wave), before);
+
+        wave.set_deadline(None);
+        run(&mut wave).unwrap();
+        assert!(wave.is_collapsed());
+    }
+
+    #[test]
+    fn test_seed() {
+        let mut a = Wave::new(&tiles(), 10, 10);
+        let mut b = Wave::new(&tiles(), 10, 10);
+        a.set_seed(42);
+        b.set_seed(42);
+        a.initialize();
+        b.initialize();
+
+        assert_eq!(run(&mut a), run(&mut b));
+        assert_eq!(indices(&a), indices(&b));
+        assert!(is_valid(&a));
+    }
+
+    #[test]
+    fn test_registry() {
+        let wave = Wave::new(&tiles(), 3, 3);
+        let custom = Wave::with_custom_constraint(&tiles(), 3, 3, get_constraint_fn(3));
+        // Registering does not change the connectors.
+        assert_eq!(wave.input[1].connectors, custom.input[1].connectors);
+
+        let up = wave.input[1].connectors[Direction::Up as usize];
+        assert_eq!(wave.connector_name(up), "#000000FF #000000FF #000000FF");
+        assert_eq!(custom.connector_name(up), up.to_string());
+        assert!(wave.collisions().is_empty());
+        assert!(custom.collisions().is_empty());
+    }
+
+    #[test]
+    fn test_try_new() {
+        struct Compressed;
+
+        impl Image for Compressed {
+            fn size(&self) -> (usize, usize) {
+                (3, 3)
+            }
+            fn get_pixel_at(&self, _x: usize, _y: usize) -> [u8; 4] {
+                [0; 4]
+            }
+            fn format(&self) -> Result<PixelFormat, String> {
+                Err(String::from("Unsupported format"))
+            }
+        }
+
+        assert!(Wave::try_new(&tiles(), 3, 3).is_ok());
+        let err = Wave::try_new(&[Compressed], 3, 3).err().unwrap();
+        assert_eq!(err, "Image 0: Unsupported format");
+    }
+
+    #[test]
+    fn test_payloads() {
+        let names = vec!["blank", "h", "v", "a", "b", "full"];
+        assert!(Wave::new(&tiles(), 3, 3).with_payloads(vec![0]).is_err());
+
+        let mut wave = Wave::new(&tiles(), 3, 3).with_payloads(names).unwrap();
+        wave.set_seed(3);
+        wave.initialize();
+        assert_eq!(wave.payloads().iter().flatten().count(), 1);
+        assert_eq!(wave.payload_at(3, 0), None);
+
+        let mut wave = wave.with_payloads((0..6).collect::<Vec<usize>>()).unwrap();
+        run(&mut wave).unwrap();
+        for (slot, payload) in wave.grid.iter().zip(wave.payloads()) {
+            assert_eq!(payload, Some(&slot.superposition[0].index));
+        }
+    }
+
+    #[test]
+    fn test_periodic() {
+        let (width, height) = (4, 3);
+        let mut wave = Wave::new(&tiles(), width, height);
+        wave.set_periodic(true);
+        solve(&mut wave, 100);
+
+        let connector = |x: usize, y: usize, direction: Direction| {
+            wave.grid[x + y * width].superposition[0].connectors[direction as usize]
+        };
+        for y in 0..height {
+            assert_eq!(
+                connector(width - 1, y, Direction::Right),
+                connector(0, y, Direction::Left)
+            );
+        }
+        for x in 0..width {
+            assert_eq!(
+                connector(x, height - 1, Direction::Down),
+                connector(x, 0, Direction::Up)
+            );
+        }
+    }
+
+    #[test]
+    fn test_scanline() {
+        // Tiles that fit everywhere, so only observed slots are collapsed.
+        let mut wave = Wave::new(&[Edges([0; 4]), Edges([0; 4])], 5, 5);
+        wave.set_heuristic(Heuristic::Scanline);
+        wave.initialize();
+        // The first iteration only propagates the slot collapsed by `initialize`.
+        wave.collapse_with(4, |_| {}).expect("Failed to collapse");
+
+        assert!(wave.grid[..3]
+            .iter()
+            .all(|slot| slot.superposition.len() == 1));
+        assert!(
+            wave.grid[4..]
+                .iter()
+                .filter(|slot| slot.superposition.len() == 1)
+                .count()
+                <= 1
+        );
+    }
+
+    #[test]
+    fn test_no_modules() {
+        let mut wave = Wave::new(&[] as &[Edges], 3, 2);
+        wave.initialize();
+        assert!(!wave.is_collapsed());
+        assert!(matches!(
+            wave.collapse(10),
+            Err(CollapseError::Contradiction(_))
+        ));
+        assert!(wave.run_steps(10).is_err());
+        assert!(wave.run_for(Duration::from_secs(1)).is_err());
+        for heuristic in [Heuristic::Scanline, Heuristic::Random] {
+            wave.set_heuristic(heuristic);
+            assert!(wave.collapse(1).is_err());
+        }
+        #[cfg(feature = "rayon")]
+        assert!(wave.collapse_regions(2, 0).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_resume() {
+        let mut wave = Wave::new(&tiles(), 10, 10);
+        wave.set_seed(7);
+        wave.initialize();
+        wave.collapse(5).expect("Failed to collapse");
+
+        let saved = serde_json::to_string(&wave).expect("Failed to serialize");
+        let mut resumed: Wave = serde_json::from_str(&saved).expect("Failed to deserialize");
+        assert_eq!(indices(&wave), indices(&resumed));
+
+        assert_eq!(run(&mut wave), run(&mut resumed));
+        assert_eq!(indices(&wave), indices(&resumed));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_resume_custom_possible_fn() {
+        let is_possible_fn: Arc<PossibleFn> =
+            Arc::new(|module, from, _to, d| module.is_possible(&from, d));
+        let mut wave = Wave::new(&tiles(), 10, 10);
+        wave.is_possible_fn = is_possible_fn.clone();
+        wave.set_seed(7);
+        wave.initialize();
+        wave.collapse(5).expect("Failed to collapse");
+
+        let saved = serde_json::to_string(&wave).expect("Failed to serialize");
+        let mut resumed: Wave = serde_json::from_str(&saved).expect("Failed to deserialize");
+        assert!(matches!(
+            resumed.collapse(1),
+            Err(CollapseError::Contradiction(_))
+        ));
+
+        resumed.is_possible_fn = is_possible_fn;
+        assert_eq!(run(&mut wave), run(&mut resumed));
+        assert_eq!(indices(&wave), indices(&resumed));
+    }
 }